@@ -4,26 +4,29 @@ use pest::iterators::Pair;
 use quick_impl::quick_impl_all;
 
 use super::variable::RVariable;
-use super::{RCompileVariable, RHexLiteral, RStringLiteral};
+use super::{RCompileVariable, RDecimalLiteral, RHexLiteral, RStringLiteral};
 use crate::parser::error::PestError;
 use crate::parser::parser::{FromPair, Located, Rule, get_next, map_unique_child};
+use crate::parser::source::FileId;
 
 #[derive(Debug, Clone)]
 #[quick_impl_all(impl From)]
 pub enum RAttributeEqualityRight {
     HexLiteral(RHexLiteral),
+    DecimalLiteral(RDecimalLiteral),
     CompileVariable(RCompileVariable),
     StringLiteral(#[allow(unused)] RStringLiteral),
 }
 
 impl FromPair for RAttributeEqualityRight {
-    fn from_pair(attribute_equality_right: Pair<Rule>) -> Result<Self, PestError> {
+    fn from_pair(attribute_equality_right: Pair<Rule>, file: FileId) -> Result<Self, PestError> {
         assert!(attribute_equality_right.as_rule() == Rule::attribute_equality_right);
 
         map_unique_child(attribute_equality_right, |inner| match inner.as_rule() {
-            Rule::hex_literal => Ok(RHexLiteral::from_pair(inner)?.into()),
-            Rule::compile_variable => Ok(RCompileVariable::from_pair(inner)?.into()),
-            Rule::string_literal => Ok(RStringLiteral::from_pair(inner)?.into()),
+            Rule::hex_literal => Ok(RHexLiteral::from_pair(inner, file)?.into()),
+            Rule::decimal_literal => Ok(RDecimalLiteral::from_pair(inner, file)?.into()),
+            Rule::compile_variable => Ok(RCompileVariable::from_pair(inner, file)?.into()),
+            Rule::string_literal => Ok(RStringLiteral::from_pair(inner, file)?.into()),
             _ => unreachable!(),
         })
     }
@@ -42,19 +45,19 @@ impl RAttributeEquality {
 }
 
 impl FromPair for RAttributeEquality {
-    fn from_pair(attribute_equality: Pair<Rule>) -> Result<Self, PestError> {
+    fn from_pair(attribute_equality: Pair<Rule>, file: FileId) -> Result<Self, PestError> {
         assert!(attribute_equality.as_rule() == Rule::attribute_equality);
 
         let mut inner = attribute_equality.into_inner();
 
-        let name = Located::<RVariable>::from_pair(get_next(&mut inner, Rule::variable))?;
+        let name = Located::<RVariable>::from_pair(get_next(&mut inner, Rule::variable), file)?;
 
         _ = get_next(&mut inner, Rule::eq);
 
-        let value = Located::<RAttributeEqualityRight>::from_pair(get_next(
-            &mut inner,
-            Rule::attribute_equality_right,
-        ))?;
+        let value = Located::<RAttributeEqualityRight>::from_pair(
+            get_next(&mut inner, Rule::attribute_equality_right),
+            file,
+        )?;
 
         assert!(inner.next().is_none());
 
@@ -62,22 +65,133 @@ impl FromPair for RAttributeEquality {
     }
 }
 
+/// Equality on a compile variable - ex: `$CHAINID$ = 0x01`.
+#[derive(Debug, Clone)]
+pub struct RCompileVariableEquality {
+    pub name: Located<RCompileVariable>,
+    pub value: Located<RAttributeEqualityRight>,
+}
+
+impl FromPair for RCompileVariableEquality {
+    fn from_pair(compile_variable_equality: Pair<Rule>, file: FileId) -> Result<Self, PestError> {
+        assert!(compile_variable_equality.as_rule() == Rule::compile_variable_equality);
+
+        let mut inner = compile_variable_equality.into_inner();
+
+        let name = Located::<RCompileVariable>::from_pair(
+            get_next(&mut inner, Rule::compile_variable),
+            file,
+        )?;
+
+        _ = get_next(&mut inner, Rule::eq);
+
+        let value = Located::<RAttributeEqualityRight>::from_pair(
+            get_next(&mut inner, Rule::attribute_equality_right),
+            file,
+        )?;
+
+        assert!(inner.next().is_none());
+
+        Ok(Self { name, value })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RComparisonOperator {
+    Le,
+    Ge,
+    Lt,
+    Gt,
+}
+
+impl FromPair for RComparisonOperator {
+    fn from_pair(comparison_operator: Pair<Rule>, _file: FileId) -> Result<Self, PestError> {
+        assert!(comparison_operator.as_rule() == Rule::comparison_operator);
+
+        map_unique_child(comparison_operator, |inner| match inner.as_rule() {
+            Rule::le => Ok(Self::Le),
+            Rule::ge => Ok(Self::Ge),
+            Rule::lt => Ok(Self::Lt),
+            Rule::gt => Ok(Self::Gt),
+            _ => unreachable!(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RAttributeComparison {
+    pub name: Located<RVariable>,
+    pub operator: Located<RComparisonOperator>,
+    pub value: Located<RAttributeEqualityRight>,
+}
+
+impl FromPair for RAttributeComparison {
+    fn from_pair(attribute_comparison: Pair<Rule>, file: FileId) -> Result<Self, PestError> {
+        assert!(attribute_comparison.as_rule() == Rule::attribute_comparison);
+
+        let mut inner = attribute_comparison.into_inner();
+
+        let name = Located::<RVariable>::from_pair(get_next(&mut inner, Rule::variable), file)?;
+
+        let operator = Located::<RComparisonOperator>::from_pair(
+            get_next(&mut inner, Rule::comparison_operator),
+            file,
+        )?;
+
+        let value = Located::<RAttributeEqualityRight>::from_pair(
+            get_next(&mut inner, Rule::attribute_equality_right),
+            file,
+        )?;
+
+        assert!(inner.next().is_none());
+
+        Ok(Self {
+            name,
+            operator,
+            value,
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 #[quick_impl_all(impl From)]
 pub enum RAttributeArg {
     AttributeEquality(RAttributeEquality),
+    AttributeComparison(RAttributeComparison),
     Variable(RVariable),
-    StringLiteral(#[allow(unused)] RStringLiteral),
+    StringLiteral(RStringLiteral),
+    DecimalLiteral(RDecimalLiteral),
+    CompileVariable(RCompileVariable),
+    CompileVariableEquality(RCompileVariableEquality),
+    Not(Box<Located<RAttributeArg>>),
 }
 
 impl FromPair for RAttributeArg {
-    fn from_pair(attribute_arg: Pair<Rule>) -> Result<Self, PestError> {
+    fn from_pair(attribute_arg: Pair<Rule>, file: FileId) -> Result<Self, PestError> {
         assert!(attribute_arg.as_rule() == Rule::attribute_arg);
 
         map_unique_child(attribute_arg, |inner| match inner.as_rule() {
-            Rule::attribute_equality => Ok(RAttributeEquality::from_pair(inner)?.into()),
-            Rule::variable => Ok(RVariable::from_pair(inner)?.into()),
-            Rule::string_literal => Ok(RStringLiteral::from_pair(inner)?.into()),
+            Rule::attribute_equality => Ok(RAttributeEquality::from_pair(inner, file)?.into()),
+            Rule::attribute_comparison => Ok(RAttributeComparison::from_pair(inner, file)?.into()),
+            Rule::variable => Ok(RVariable::from_pair(inner, file)?.into()),
+            Rule::string_literal => Ok(RStringLiteral::from_pair(inner, file)?.into()),
+            Rule::decimal_literal => Ok(RDecimalLiteral::from_pair(inner, file)?.into()),
+            Rule::compile_variable => Ok(RCompileVariable::from_pair(inner, file)?.into()),
+            Rule::compile_variable_equality => {
+                Ok(RCompileVariableEquality::from_pair(inner, file)?.into())
+            }
+            Rule::attribute_not => {
+                let mut not_inner = inner.into_inner();
+                _ = get_next(&mut not_inner, Rule::not_keyword);
+                _ = get_next(&mut not_inner, Rule::open_paren);
+                let arg = Located::<RAttributeArg>::from_pair(
+                    get_next(&mut not_inner, Rule::attribute_arg),
+                    file,
+                )?;
+                _ = get_next(&mut not_inner, Rule::close_paren);
+                assert!(not_inner.next().is_none());
+                Ok(Box::new(arg).into())
+            }
             _ => unreachable!(),
         })
     }
@@ -86,38 +200,48 @@ impl FromPair for RAttributeArg {
 #[derive(Debug, Clone)]
 pub struct RAttribute {
     pub name: Located<RVariable>,
-    pub arg: Option<Located<RAttributeArg>>,
+    pub args: Vec<Located<RAttributeArg>>,
 }
 
 impl RAttribute {
     pub fn name_str(&self) -> &str {
         self.name.as_str()
     }
+
+    /// The first argument, for the attributes taking a single one.
+    pub fn arg(&self) -> Option<&Located<RAttributeArg>> {
+        self.args.first()
+    }
 }
 
 impl FromPair for RAttribute {
-    fn from_pair(attribute: Pair<Rule>) -> Result<Self, PestError> {
-        assert!(attribute.as_rule() == Rule::attribute);
+    fn from_pair(attribute: Pair<Rule>, file: FileId) -> Result<Self, PestError> {
+        assert!(matches!(
+            attribute.as_rule(),
+            Rule::attribute | Rule::file_attribute
+        ));
 
         let mut attribute_inner = attribute.into_inner();
 
-        let name = Located::<RVariable>::from_pair(get_next(&mut attribute_inner, Rule::variable))?;
+        let name =
+            Located::<RVariable>::from_pair(get_next(&mut attribute_inner, Rule::variable), file)?;
 
+        let mut args = Vec::<Located<RAttributeArg>>::new();
         if let Some(paren) = attribute_inner.next() {
             assert!(paren.as_rule() == Rule::open_paren);
 
-            let arg = Some(Located::<RAttributeArg>::from_pair(get_next(
-                &mut attribute_inner,
-                Rule::attribute_arg,
-            ))?);
-
-            _ = get_next(&mut attribute_inner, Rule::close_paren);
-            assert!(attribute_inner.next().is_none());
-
-            Ok(Self { name, arg })
-        } else {
-            Ok(Self { name, arg: None })
+            for arg_or_separator in attribute_inner {
+                match arg_or_separator.as_rule() {
+                    Rule::attribute_arg => {
+                        args.push(Located::<RAttributeArg>::from_pair(arg_or_separator, file)?)
+                    }
+                    Rule::comma | Rule::close_paren => (),
+                    _ => unreachable!(),
+                }
+            }
         }
+
+        Ok(Self { name, args })
     }
 }
 
@@ -128,17 +252,17 @@ pub struct WithAttributes<T> {
 }
 
 impl<T: FromPair> FromPair for WithAttributes<T> {
-    fn from_pair(item_with_attr: Pair<Rule>) -> Result<Self, PestError> {
+    fn from_pair(item_with_attr: Pair<Rule>, file: FileId) -> Result<Self, PestError> {
         let mut inner = item_with_attr.into_inner();
 
         let mut attributes = Vec::<Located<RAttribute>>::new();
         while let Some(attr_or_item) = inner.next() {
             match attr_or_item.as_rule() {
                 Rule::attribute => {
-                    attributes.push(Located::<RAttribute>::from_pair(attr_or_item)?);
+                    attributes.push(Located::<RAttribute>::from_pair(attr_or_item, file)?);
                 }
                 _ => {
-                    let attr_inner = T::from_pair(attr_or_item)?;
+                    let attr_inner = T::from_pair(attr_or_item, file)?;
                     assert!(inner.next().is_none());
                     return Ok(Self {
                         attributes,
@@ -1,12 +1,14 @@
 use pest::iterators::Pair;
 use quick_impl::quick_impl_all;
 
+use super::RExpression;
+use super::RFunction;
 use super::RHexAlias;
 use super::attribute::WithAttributes;
-use super::function::RFunction;
 use super::variable::{RVariable, RVariableWithField};
-use crate::parser::error::PestError;
+use crate::parser::error::{PestError, new_error_from_pair};
 use crate::parser::parser::{FromPair, Located, Rule, get_next, map_unique_child};
+use crate::parser::source::FileId;
 
 #[derive(Debug, Clone)]
 #[quick_impl_all(impl From)]
@@ -15,30 +17,73 @@ pub enum RBlockRefStar {
 }
 
 impl FromPair for RBlockRefStar {
-    fn from_pair(block_ref_star: Pair<Rule>) -> Result<Self, PestError> {
+    fn from_pair(block_ref_star: Pair<Rule>, file: FileId) -> Result<Self, PestError> {
         assert!(block_ref_star.as_rule() == Rule::block_ref_star);
 
         map_unique_child(block_ref_star, |child| match child.as_rule() {
-            Rule::variable => Ok(RVariable::from_pair(child)?.into()),
+            Rule::variable => Ok(RVariable::from_pair(child, file)?.into()),
             _ => unreachable!(),
         })
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct RBlockCall {
+    pub name: Located<RVariable>,
+    pub args: Vec<Located<RExpression>>,
+}
+
+impl FromPair for RBlockCall {
+    fn from_pair(block_call: Pair<Rule>, file: FileId) -> Result<Self, PestError> {
+        assert!(block_call.as_rule() == Rule::block_call);
+
+        let mut block_call_inner = block_call.into_inner();
+
+        let name =
+            Located::<RVariable>::from_pair(get_next(&mut block_call_inner, Rule::variable), file)?;
+
+        let mut block_call_args_inner =
+            get_next(&mut block_call_inner, Rule::block_call_args).into_inner();
+        assert!(block_call_inner.next().is_none());
+
+        _ = get_next(&mut block_call_args_inner, Rule::open_paren);
+
+        let mut args = vec![Located::<RExpression>::from_pair(
+            get_next(&mut block_call_args_inner, Rule::expression),
+            file,
+        )?];
+
+        while let Some(comma_or_paren) = block_call_args_inner.next() {
+            match comma_or_paren.as_rule() {
+                Rule::comma => args.push(Located::<RExpression>::from_pair(
+                    get_next(&mut block_call_args_inner, Rule::expression),
+                    file,
+                )?),
+                Rule::close_paren => assert!(block_call_args_inner.next().is_none()),
+                _ => unreachable!(),
+            }
+        }
+
+        Ok(Self { name, args })
+    }
+}
+
 #[derive(Debug, Clone)]
 #[quick_impl_all(impl From)]
 pub enum RBlockRefEsp {
     Variable(RVariable),
     VariableWithField(RVariableWithField),
+    Call(RBlockCall),
 }
 
 impl FromPair for RBlockRefEsp {
-    fn from_pair(block_ref_esp: Pair<Rule>) -> Result<Self, PestError> {
+    fn from_pair(block_ref_esp: Pair<Rule>, file: FileId) -> Result<Self, PestError> {
         assert!(block_ref_esp.as_rule() == Rule::block_ref_esp);
 
         map_unique_child(block_ref_esp, |child| match child.as_rule() {
-            Rule::variable => Ok(RVariable::from_pair(child)?.into()),
-            Rule::variable_with_field => Ok(RVariableWithField::from_pair(child)?.into()),
+            Rule::variable => Ok(RVariable::from_pair(child, file)?.into()),
+            Rule::variable_with_field => Ok(RVariableWithField::from_pair(child, file)?.into()),
+            Rule::block_call => Ok(RBlockCall::from_pair(child, file)?.into()),
             _ => unreachable!(),
         })
     }
@@ -52,14 +97,14 @@ pub enum RBlockRef {
 }
 
 impl FromPair for RBlockRef {
-    fn from_pair(block_ref: Pair<Rule>) -> Result<Self, PestError> {
+    fn from_pair(block_ref: Pair<Rule>, file: FileId) -> Result<Self, PestError> {
         assert!(block_ref.as_rule() == Rule::block_ref);
 
         let mut inner = block_ref.into_inner();
 
         let res = match inner.next().unwrap().as_rule() {
-            Rule::star => RBlockRefStar::from_pair(inner.next().unwrap())?.into(),
-            Rule::esp => RBlockRefEsp::from_pair(inner.next().unwrap())?.into(),
+            Rule::star => RBlockRefStar::from_pair(inner.next().unwrap(), file)?.into(),
+            Rule::esp => RBlockRefEsp::from_pair(inner.next().unwrap(), file)?.into(),
             _ => unreachable!(),
         };
 
@@ -69,32 +114,140 @@ impl FromPair for RBlockRef {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct RLabel {
+    pub name: Located<RVariable>,
+}
+
+impl FromPair for RLabel {
+    fn from_pair(label_decl: Pair<Rule>, file: FileId) -> Result<Self, PestError> {
+        assert!(label_decl.as_rule() == Rule::label_decl);
+
+        let mut label_decl_inner = label_decl.into_inner();
+
+        _ = get_next(&mut label_decl_inner, Rule::label_keyword);
+
+        let name =
+            Located::<RVariable>::from_pair(get_next(&mut label_decl_inner, Rule::variable), file)?;
+
+        _ = get_next(&mut label_decl_inner, Rule::colon);
+        assert!(label_decl_inner.next().is_none());
+
+        Ok(Self { name })
+    }
+}
+
+impl RLabel {
+    pub fn name_str(&self) -> &str {
+        self.name.as_str()
+    }
+}
+
+/// Items repeated for each value of the loop variable, from `start` included to `end` excluded.
+#[derive(Debug, Clone)]
+pub struct RRepeat {
+    pub variable: Located<RVariable>,
+    pub start: Located<RExpression>,
+    pub end: Located<RExpression>,
+    pub items: Vec<Located<WithAttributes<Located<RBlockItem>>>>,
+}
+
+impl FromPair for RRepeat {
+    fn from_pair(repeat_decl: Pair<Rule>, file: FileId) -> Result<Self, PestError> {
+        assert!(repeat_decl.as_rule() == Rule::repeat_decl);
+
+        let mut repeat_decl_inner = repeat_decl.into_inner();
+
+        _ = get_next(&mut repeat_decl_inner, Rule::repeat_keyword);
+        _ = get_next(&mut repeat_decl_inner, Rule::open_paren);
+
+        let variable = Located::<RVariable>::from_pair(
+            get_next(&mut repeat_decl_inner, Rule::variable),
+            file,
+        )?;
+
+        _ = get_next(&mut repeat_decl_inner, Rule::in_keyword);
+
+        let start = Located::<RExpression>::from_pair(
+            get_next(&mut repeat_decl_inner, Rule::expression),
+            file,
+        )?;
+
+        _ = get_next(&mut repeat_decl_inner, Rule::range);
+
+        let end = Located::<RExpression>::from_pair(
+            get_next(&mut repeat_decl_inner, Rule::expression),
+            file,
+        )?;
+
+        _ = get_next(&mut repeat_decl_inner, Rule::close_paren);
+        _ = get_next(&mut repeat_decl_inner, Rule::open_brace);
+
+        let mut items = Vec::<Located<WithAttributes<Located<RBlockItem>>>>::new();
+        for block_item_with_attr in repeat_decl_inner {
+            match block_item_with_attr.as_rule() {
+                Rule::block_item_with_attr => {
+                    items.push(Located::<WithAttributes<Located<RBlockItem>>>::from_pair(
+                        block_item_with_attr,
+                        file,
+                    )?);
+                }
+                Rule::close_brace => (),
+                _ => unreachable!(),
+            }
+        }
+
+        Ok(Self {
+            variable,
+            start,
+            end,
+            items,
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 #[quick_impl_all(impl From)]
 pub enum RBlockItem {
+    Label(RLabel),
     Function(RFunction),
     HexAlias(RHexAlias),
     BlockRef(RBlockRef),
+    /// Entry of a table, written on the width of the table.
+    TableEntry(RExpression),
+    Repeat(RRepeat),
 }
 
 impl FromPair for RBlockItem {
-    fn from_pair(block_item: Pair<Rule>) -> Result<Self, PestError> {
+    fn from_pair(block_item: Pair<Rule>, file: FileId) -> Result<Self, PestError> {
+        if block_item.as_rule() == Rule::repeat_decl {
+            return Ok(RRepeat::from_pair(block_item, file)?.into());
+        }
         assert!(block_item.as_rule() == Rule::block_item);
 
         map_unique_child(block_item, |child| match child.as_rule() {
-            Rule::function => Ok(RFunction::from_pair(child)?.into()),
-            Rule::hex_alias => Ok(RHexAlias::from_pair(child)?.into()),
-            Rule::block_ref => Ok(RBlockRef::from_pair(child)?.into()),
+            Rule::label_decl => Ok(RLabel::from_pair(child, file)?.into()),
+            Rule::function => Ok(RFunction::from_pair(child, file)?.into()),
+            Rule::hex_alias => Ok(RHexAlias::from_pair(child, file)?.into()),
+            Rule::block_ref => Ok(RBlockRef::from_pair(child, file)?.into()),
             _ => unreachable!(),
         })
     }
 }
 
+/// Default number of bytes of the entries of a table.
+pub const DEFAULT_TABLE_WIDTH: usize = 2;
+
 #[derive(Debug, Clone)]
 pub struct RBlock {
     pub name: Located<RVariable>,
     pub abstr: bool,
+    /// Whether the block only holds data, placed after the code.
+    pub data: bool,
+    pub params: Vec<Located<RVariable>>,
     pub items: Vec<Located<WithAttributes<Located<RBlockItem>>>>,
+    /// Width of the entries if the block is a table, whose items are only table entries.
+    pub table_width: Option<usize>,
 }
 
 impl RBlock {
@@ -104,24 +257,44 @@ impl RBlock {
 }
 
 impl FromPair for RBlock {
-    fn from_pair(block_decl: Pair<Rule>) -> Result<Self, PestError> {
+    fn from_pair(block_decl: Pair<Rule>, file: FileId) -> Result<Self, PestError> {
+        if block_decl.as_rule() == Rule::table_decl {
+            return table_from_pair(block_decl, file);
+        }
         assert!(block_decl.as_rule() == Rule::block_decl);
 
         let mut block_decl_inner = block_decl.into_inner();
 
-        let abstr = match block_decl_inner.next().unwrap().as_rule() {
-            Rule::block_keyword => false,
+        let (abstr, data) = match block_decl_inner.next().unwrap().as_rule() {
+            Rule::block_keyword => (false, false),
             Rule::abstract_keyword => {
                 _ = get_next(&mut block_decl_inner, Rule::block_keyword);
-                true
+                (true, false)
+            }
+            Rule::data_keyword => {
+                _ = get_next(&mut block_decl_inner, Rule::block_keyword);
+                (false, true)
             }
             _ => unreachable!(),
         };
 
         let name =
-            Located::<RVariable>::from_pair(get_next(&mut block_decl_inner, Rule::variable))?;
+            Located::<RVariable>::from_pair(get_next(&mut block_decl_inner, Rule::variable), file)?;
 
-        _ = get_next(&mut block_decl_inner, Rule::open_brace);
+        let mut params = Vec::<Located<RVariable>>::new();
+        let open_brace_or_params = block_decl_inner.next().unwrap();
+        if open_brace_or_params.as_rule() == Rule::block_params {
+            for param in open_brace_or_params.into_inner() {
+                match param.as_rule() {
+                    Rule::variable => params.push(Located::<RVariable>::from_pair(param, file)?),
+                    Rule::open_paren | Rule::comma | Rule::close_paren => (),
+                    _ => unreachable!(),
+                }
+            }
+            _ = get_next(&mut block_decl_inner, Rule::open_brace);
+        } else {
+            assert!(open_brace_or_params.as_rule() == Rule::open_brace);
+        }
 
         let mut items = Vec::<Located<WithAttributes<Located<RBlockItem>>>>::new();
         while let Some(block_item_with_attr) = block_decl_inner.next() {
@@ -129,11 +302,19 @@ impl FromPair for RBlock {
                 Rule::block_item_with_attr => {
                     items.push(Located::<WithAttributes<Located<RBlockItem>>>::from_pair(
                         block_item_with_attr,
+                        file,
                     )?);
                 }
                 Rule::close_brace => {
                     assert!(block_decl_inner.next().is_none());
-                    return Ok(RBlock { name, abstr, items });
+                    return Ok(RBlock {
+                        name,
+                        abstr,
+                        data,
+                        params,
+                        items,
+                        table_width: None,
+                    });
                 }
                 _ => unreachable!(),
             }
@@ -141,3 +322,73 @@ impl FromPair for RBlock {
         unreachable!();
     }
 }
+
+fn table_from_pair(table_decl: Pair<Rule>, file: FileId) -> Result<RBlock, PestError> {
+    assert!(table_decl.as_rule() == Rule::table_decl);
+
+    let mut table_decl_inner = table_decl.into_inner();
+
+    _ = get_next(&mut table_decl_inner, Rule::table_keyword);
+
+    let name =
+        Located::<RVariable>::from_pair(get_next(&mut table_decl_inner, Rule::variable), file)?;
+
+    let mut table_width = DEFAULT_TABLE_WIDTH;
+    let open_brace_or_width = table_decl_inner.next().unwrap();
+    if open_brace_or_width.as_rule() == Rule::table_width {
+        let mut table_width_inner = open_brace_or_width.into_inner();
+        _ = get_next(&mut table_width_inner, Rule::open_paren);
+        let width_name = get_next(&mut table_width_inner, Rule::variable);
+        if width_name.as_str() != "width" {
+            return Err(new_error_from_pair(
+                &width_name,
+                "Expected `width` - ex: table name(width = 2) { a, b }".to_owned(),
+            ));
+        }
+        _ = get_next(&mut table_width_inner, Rule::eq);
+        let width_literal = get_next(&mut table_width_inner, Rule::decimal_literal);
+        table_width = match width_literal.as_str().parse::<usize>() {
+            Ok(width) if (1..=32).contains(&width) => width,
+            _ => {
+                return Err(new_error_from_pair(
+                    &width_literal,
+                    "The width of a table must be between 1 and 32 bytes.".to_owned(),
+                ));
+            }
+        };
+        _ = get_next(&mut table_decl_inner, Rule::open_brace);
+    } else {
+        assert!(open_brace_or_width.as_rule() == Rule::open_brace);
+    }
+
+    let mut items = Vec::<Located<WithAttributes<Located<RBlockItem>>>>::new();
+    for entry in table_decl_inner {
+        match entry.as_rule() {
+            Rule::expression => {
+                let Located { location, inner } = Located::<RExpression>::from_pair(entry, file)?;
+                let item = Located {
+                    location: location.clone(),
+                    inner: RBlockItem::TableEntry(inner),
+                };
+                items.push(Located {
+                    location,
+                    inner: WithAttributes {
+                        attributes: Vec::new(),
+                        inner: item,
+                    },
+                });
+            }
+            Rule::comma | Rule::close_brace => (),
+            _ => unreachable!(),
+        }
+    }
+
+    Ok(RBlock {
+        name,
+        abstr: false,
+        data: false,
+        params: Vec::new(),
+        items,
+        table_width: Some(table_width),
+    })
+}
@@ -4,14 +4,20 @@ use super::variable::RVariable;
 use crate::ast::attribute::WithAttributes;
 use crate::ast::block::RBlock;
 use crate::ast::constant::RConstant;
+use crate::ast::immutable::RImmutable;
+use crate::ast::link::RLink;
 use crate::parser::error::PestError;
 use crate::parser::parser::{FromPair, Located, Rule, get_next};
+use crate::parser::source::FileId;
 
 #[derive(Debug, Clone)]
 pub struct RContract {
     pub name: Located<RVariable>,
+    pub bases: Vec<Located<RVariable>>,
     pub blocks: Vec<Located<WithAttributes<Located<RBlock>>>>,
     pub constants: Vec<Located<RConstant>>,
+    pub immutables: Vec<Located<RImmutable>>,
+    pub links: Vec<Located<RLink>>,
 }
 
 impl RContract {
@@ -21,7 +27,7 @@ impl RContract {
 }
 
 impl FromPair for RContract {
-    fn from_pair(contract_decl: Pair<Rule>) -> Result<Self, PestError>
+    fn from_pair(contract_decl: Pair<Rule>, file: FileId) -> Result<Self, PestError>
     where
         Self: Sized,
     {
@@ -31,29 +37,63 @@ impl FromPair for RContract {
 
         _ = get_next(&mut contract_decl_inner, Rule::contract_keyword);
 
-        let name =
-            Located::<RVariable>::from_pair(get_next(&mut contract_decl_inner, Rule::variable))?;
+        let name = Located::<RVariable>::from_pair(
+            get_next(&mut contract_decl_inner, Rule::variable),
+            file,
+        )?;
 
-        _ = get_next(&mut contract_decl_inner, Rule::open_brace);
+        let mut bases = Vec::<Located<RVariable>>::new();
+        if let Some(contract_bases) = contract_decl_inner.next() {
+            if contract_bases.as_rule() == Rule::contract_bases {
+                let mut contract_bases_inner = contract_bases.into_inner();
+                _ = get_next(&mut contract_bases_inner, Rule::is_keyword);
+                bases.push(Located::<RVariable>::from_pair(
+                    get_next(&mut contract_bases_inner, Rule::variable),
+                    file,
+                )?);
+                while let Some(comma) = contract_bases_inner.next() {
+                    assert!(comma.as_rule() == Rule::comma);
+                    bases.push(Located::<RVariable>::from_pair(
+                        get_next(&mut contract_bases_inner, Rule::variable),
+                        file,
+                    )?);
+                }
+                _ = get_next(&mut contract_decl_inner, Rule::open_brace);
+            } else {
+                assert!(contract_bases.as_rule() == Rule::open_brace);
+            }
+        }
 
         let mut blocks = Vec::<Located<WithAttributes<Located<RBlock>>>>::new();
         let mut constants = Vec::<Located<RConstant>>::new();
+        let mut immutables = Vec::<Located<RImmutable>>::new();
+        let mut links = Vec::<Located<RLink>>::new();
         while let Some(contract_item) = contract_decl_inner.next() {
             match contract_item.as_rule() {
                 Rule::block_decl_with_attr => {
                     blocks.push(Located::<WithAttributes<Located<RBlock>>>::from_pair(
                         contract_item,
+                        file,
                     )?);
                 }
                 Rule::const_decl => {
-                    constants.push(Located::<RConstant>::from_pair(contract_item)?);
+                    constants.push(Located::<RConstant>::from_pair(contract_item, file)?);
+                }
+                Rule::immutable_decl => {
+                    immutables.push(Located::<RImmutable>::from_pair(contract_item, file)?);
+                }
+                Rule::link_decl => {
+                    links.push(Located::<RLink>::from_pair(contract_item, file)?);
                 }
                 Rule::close_brace => {
                     assert!(contract_decl_inner.next().is_none());
                     return Ok(Self {
                         name,
+                        bases,
                         blocks,
                         constants,
+                        immutables,
+                        links,
                     });
                 }
                 _ => unreachable!(),
@@ -0,0 +1,182 @@
+use std::sync::LazyLock;
+
+use pest::iterators::{Pair, Pairs};
+use pest::pratt_parser::{Assoc, Op, PrattParser};
+use quick_impl::quick_impl_all;
+
+use super::variable::{RVariable, RVariableWithField};
+use super::{RCompileVariable, RDecimalLiteral, RHexLiteral, RStringLiteral};
+use crate::parser::error::PestError;
+use crate::parser::parser::{FromPair, Located, Location, Rule, get_next};
+use crate::parser::source::FileId;
+
+static PRATT_PARSER: LazyLock<PrattParser<Rule>> = LazyLock::new(|| {
+    PrattParser::new()
+        .op(Op::infix(Rule::at, Assoc::Left))
+        .op(Op::infix(Rule::bit_or, Assoc::Left))
+        .op(Op::infix(Rule::bit_xor, Assoc::Left))
+        .op(Op::infix(Rule::bit_and, Assoc::Left))
+        .op(Op::infix(Rule::shl, Assoc::Left) | Op::infix(Rule::shr, Assoc::Left))
+        .op(Op::infix(Rule::add, Assoc::Left) | Op::infix(Rule::sub, Assoc::Left))
+        .op(Op::infix(Rule::mul, Assoc::Left)
+            | Op::infix(Rule::div, Assoc::Left)
+            | Op::infix(Rule::rem, Assoc::Left))
+        .op(Op::prefix(Rule::neg) | Op::prefix(Rule::bit_not))
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RUnaryOperator {
+    Neg,
+    BitNot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RBinaryOperator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Shl,
+    Shr,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Concat,
+}
+
+#[derive(Debug, Clone)]
+pub struct RExpressionCall {
+    pub name: Located<RVariable>,
+    pub arg: Located<RStringLiteral>,
+}
+
+impl FromPair for RExpressionCall {
+    fn from_pair(expression_call: Pair<Rule>, file: FileId) -> Result<Self, PestError> {
+        assert!(expression_call.as_rule() == Rule::expression_call);
+
+        let mut expression_call_inner = expression_call.into_inner();
+
+        let name = Located::<RVariable>::from_pair(
+            get_next(&mut expression_call_inner, Rule::variable),
+            file,
+        )?;
+
+        _ = get_next(&mut expression_call_inner, Rule::open_paren);
+
+        let arg = Located::<RStringLiteral>::from_pair(
+            get_next(&mut expression_call_inner, Rule::string_literal),
+            file,
+        )?;
+
+        _ = get_next(&mut expression_call_inner, Rule::close_paren);
+        assert!(expression_call_inner.next().is_none());
+
+        Ok(Self { name, arg })
+    }
+}
+
+#[derive(Debug, Clone)]
+#[quick_impl_all(impl From)]
+pub enum RExpression {
+    HexLiteral(RHexLiteral),
+    DecimalLiteral(RDecimalLiteral),
+    StringLiteral(RStringLiteral),
+    CompileVariable(RCompileVariable),
+    Variable(RVariable),
+    VariableWithField(RVariableWithField),
+    Call(RExpressionCall),
+    Unary {
+        op: RUnaryOperator,
+        operand: Box<Located<RExpression>>,
+    },
+    Binary {
+        op: RBinaryOperator,
+        lhs: Box<Located<RExpression>>,
+        rhs: Box<Located<RExpression>>,
+    },
+}
+
+impl FromPair for RExpression {
+    fn from_pair(expression: Pair<Rule>, file: FileId) -> Result<Self, PestError> {
+        assert!(expression.as_rule() == Rule::expression);
+
+        Ok(parse_expression(expression.into_inner(), file)?.inner)
+    }
+}
+
+fn parse_expression(pairs: Pairs<Rule>, file: FileId) -> Result<Located<RExpression>, PestError> {
+    PRATT_PARSER
+        .map_primary(|operand| match operand.as_rule() {
+            Rule::expression => Located::<RExpression>::from_pair(operand, file),
+            Rule::hex_literal => located::<RHexLiteral>(operand, file),
+            Rule::decimal_literal => located::<RDecimalLiteral>(operand, file),
+            Rule::string_literal => located::<RStringLiteral>(operand, file),
+            Rule::compile_variable => located::<RCompileVariable>(operand, file),
+            Rule::expression_call => located::<RExpressionCall>(operand, file),
+            Rule::variable_with_field => located::<RVariableWithField>(operand, file),
+            Rule::variable => located::<RVariable>(operand, file),
+            _ => unreachable!(),
+        })
+        .map_prefix(|op, operand| {
+            let operand = operand?;
+            let op_start = op.as_span().start();
+            let op = match op.as_rule() {
+                Rule::neg => RUnaryOperator::Neg,
+                Rule::bit_not => RUnaryOperator::BitNot,
+                _ => unreachable!(),
+            };
+            Ok(Located {
+                location: Location {
+                    file: operand.location.file,
+                    start: op_start,
+                    end: operand.location.end,
+                },
+                inner: RExpression::Unary {
+                    op,
+                    operand: Box::new(operand),
+                },
+            })
+        })
+        .map_infix(|lhs, op, rhs| {
+            let (lhs, rhs) = (lhs?, rhs?);
+            let op = match op.as_rule() {
+                Rule::add => RBinaryOperator::Add,
+                Rule::sub => RBinaryOperator::Sub,
+                Rule::mul => RBinaryOperator::Mul,
+                Rule::div => RBinaryOperator::Div,
+                Rule::rem => RBinaryOperator::Rem,
+                Rule::shl => RBinaryOperator::Shl,
+                Rule::shr => RBinaryOperator::Shr,
+                Rule::bit_and => RBinaryOperator::BitAnd,
+                Rule::bit_or => RBinaryOperator::BitOr,
+                Rule::bit_xor => RBinaryOperator::BitXor,
+                Rule::at => RBinaryOperator::Concat,
+                _ => unreachable!(),
+            };
+            Ok(Located {
+                location: Location {
+                    file: lhs.location.file,
+                    start: lhs.location.start,
+                    end: rhs.location.end,
+                },
+                inner: RExpression::Binary {
+                    op,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                },
+            })
+        })
+        .parse(pairs)
+}
+
+fn located<T: FromPair + Into<RExpression>>(
+    pair: Pair<Rule>,
+    file: FileId,
+) -> Result<Located<RExpression>, PestError> {
+    let Located { location, inner } = Located::<T>::from_pair(pair, file)?;
+    Ok(Located {
+        location,
+        inner: inner.into(),
+    })
+}
@@ -2,41 +2,56 @@ use pest::Parser;
 use pest::iterators::Pair;
 
 use super::contract::RContract;
-use crate::ast::attribute::WithAttributes;
+use super::import::RImport;
+use crate::ast::attribute::{RAttribute, WithAttributes};
 use crate::parser::error::{PestError, new_generic_error};
 use crate::parser::parser::{FromPair, Located, MeplangParser, Rule};
+use crate::parser::source::FileId;
 
 #[derive(Default, Debug, Clone)]
-pub struct RFile(pub Vec<Located<WithAttributes<Located<RContract>>>>);
+pub struct RFile {
+    pub imports: Vec<Located<RImport>>,
+    pub attributes: Vec<Located<RAttribute>>,
+    pub contracts: Vec<Located<WithAttributes<Located<RContract>>>>,
+}
 
 impl RFile {
-    pub fn new(code: String) -> Result<Self, PestError> {
-        let mut pairs = MeplangParser::parse(Rule::file, &code)?;
-        let Some(file) = pairs.next() else {
+    pub fn new(code: &str, file: FileId) -> Result<Self, PestError> {
+        let mut pairs = MeplangParser::parse(Rule::file, code)?;
+        let Some(r_file) = pairs.next() else {
             return Err(new_generic_error("invalid file".to_owned()));
         };
         if pairs.next().is_some() {
             return Err(new_generic_error("invalid file".to_owned()));
         }
 
-        RFile::from_pair(file)
+        RFile::from_pair(r_file, file)
     }
 }
 
 impl FromPair for RFile {
-    fn from_pair(file: Pair<Rule>) -> Result<Self, PestError> {
+    fn from_pair(file: Pair<Rule>, file_id: FileId) -> Result<Self, PestError> {
         assert!(file.as_rule() == Rule::file);
 
+        let mut imports = Vec::<Located<RImport>>::new();
+        let mut attributes = Vec::<Located<RAttribute>>::new();
         let mut contracts = Vec::<Located<WithAttributes<Located<RContract>>>>::new();
         match file.as_rule() {
             Rule::file => {
-                for contract_decl_with_attr in file.into_inner() {
-                    match contract_decl_with_attr.as_rule() {
+                for file_item in file.into_inner() {
+                    match file_item.as_rule() {
                         Rule::EOI => (),
+                        Rule::import_decl => {
+                            imports.push(Located::<RImport>::from_pair(file_item, file_id)?);
+                        }
+                        // attributes after the last contract are file attributes too
+                        Rule::file_attribute | Rule::attribute => {
+                            attributes.push(Located::<RAttribute>::from_pair(file_item, file_id)?);
+                        }
                         Rule::contract_decl_with_attr => {
                             contracts.push(
                                 Located::<WithAttributes<Located<RContract>>>::from_pair(
-                                    contract_decl_with_attr,
+                                    file_item, file_id,
                                 )?,
                             );
                         }
@@ -46,6 +61,10 @@ impl FromPair for RFile {
             }
             _ => unreachable!(),
         }
-        Ok(Self(contracts))
+        Ok(Self {
+            imports,
+            attributes,
+            contracts,
+        })
     }
 }
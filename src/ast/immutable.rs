@@ -0,0 +1,58 @@
+use pest::iterators::Pair;
+
+use super::variable::RVariable;
+use crate::parser::error::{PestError, new_error_from_pair};
+use crate::parser::parser::{FromPair, Located, Rule, get_next};
+use crate::parser::source::FileId;
+
+/// Maximum number of bytes of an immutable, the width of a `PUSH32`.
+pub const MAX_IMMUTABLE_WIDTH: usize = 32;
+
+#[derive(Debug, Clone)]
+pub struct RImmutable {
+    pub name: Located<RVariable>,
+    /// Number of bytes reserved at each reference.
+    pub width: usize,
+}
+
+impl RImmutable {
+    pub fn name_str(&self) -> &str {
+        self.name.as_str()
+    }
+}
+
+impl FromPair for RImmutable {
+    fn from_pair(immutable_decl: Pair<Rule>, file: FileId) -> Result<RImmutable, PestError> {
+        assert!(immutable_decl.as_rule() == Rule::immutable_decl);
+
+        let mut immutable_decl_inner = immutable_decl.into_inner();
+
+        let _ = get_next(&mut immutable_decl_inner, Rule::immutable_keyword);
+
+        let name = Located::<RVariable>::from_pair(
+            get_next(&mut immutable_decl_inner, Rule::variable),
+            file,
+        )?;
+
+        let _ = get_next(&mut immutable_decl_inner, Rule::colon);
+
+        let width_literal = get_next(&mut immutable_decl_inner, Rule::decimal_literal);
+        let width = match width_literal.as_str().parse::<usize>() {
+            Ok(width) if (1..=MAX_IMMUTABLE_WIDTH).contains(&width) => width,
+            _ => {
+                return Err(new_error_from_pair(
+                    &width_literal,
+                    format!(
+                        "The width of an immutable must be between 1 and {} bytes.",
+                        MAX_IMMUTABLE_WIDTH
+                    ),
+                ));
+            }
+        };
+
+        let _ = get_next(&mut immutable_decl_inner, Rule::semicolon);
+        assert!(immutable_decl_inner.next().is_none());
+
+        Ok(Self { name, width })
+    }
+}
@@ -0,0 +1,37 @@
+use pest::iterators::Pair;
+
+use super::RStringLiteral;
+use crate::parser::error::PestError;
+use crate::parser::parser::{FromPair, Located, Rule, get_next};
+use crate::parser::source::FileId;
+
+#[derive(Debug, Clone)]
+pub struct RImport {
+    pub path: Located<RStringLiteral>,
+}
+
+impl RImport {
+    pub fn path_str(&self) -> &str {
+        &self.path.0
+    }
+}
+
+impl FromPair for RImport {
+    fn from_pair(import_decl: Pair<Rule>, file: FileId) -> Result<Self, PestError> {
+        assert!(import_decl.as_rule() == Rule::import_decl);
+
+        let mut import_decl_inner = import_decl.into_inner();
+
+        _ = get_next(&mut import_decl_inner, Rule::import_keyword);
+
+        let path = Located::<RStringLiteral>::from_pair(
+            get_next(&mut import_decl_inner, Rule::string_literal),
+            file,
+        )?;
+
+        _ = get_next(&mut import_decl_inner, Rule::semicolon);
+        assert!(import_decl_inner.next().is_none());
+
+        Ok(Self { path })
+    }
+}
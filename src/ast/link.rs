@@ -0,0 +1,36 @@
+use pest::iterators::Pair;
+
+use super::variable::RVariable;
+use crate::parser::error::PestError;
+use crate::parser::parser::{FromPair, Located, Rule, get_next};
+use crate::parser::source::FileId;
+
+/// Address of a library, pushed as zeros and patched once the library is deployed.
+#[derive(Debug, Clone)]
+pub struct RLink {
+    pub name: Located<RVariable>,
+}
+
+impl RLink {
+    pub fn name_str(&self) -> &str {
+        self.name.as_str()
+    }
+}
+
+impl FromPair for RLink {
+    fn from_pair(link_decl: Pair<Rule>, file: FileId) -> Result<RLink, PestError> {
+        assert!(link_decl.as_rule() == Rule::link_decl);
+
+        let mut link_decl_inner = link_decl.into_inner();
+
+        let _ = get_next(&mut link_decl_inner, Rule::link_keyword);
+
+        let name =
+            Located::<RVariable>::from_pair(get_next(&mut link_decl_inner, Rule::variable), file)?;
+
+        let _ = get_next(&mut link_decl_inner, Rule::semicolon);
+        assert!(link_decl_inner.next().is_none());
+
+        Ok(Self { name })
+    }
+}
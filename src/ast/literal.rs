@@ -3,6 +3,8 @@ use pest::iterators::Pair;
 
 use crate::parser::error::{PestError, new_error_from_pair};
 use crate::parser::parser::{FromPair, Rule, map_unique_child};
+use crate::parser::source::FileId;
+use crate::types::bytes32::Bytes32;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct RHexLiteral(pub Bytes);
@@ -14,7 +16,7 @@ impl From<Bytes> for RHexLiteral {
 }
 
 impl FromPair for RHexLiteral {
-    fn from_pair(hex_literal: Pair<Rule>) -> Result<Self, PestError> {
+    fn from_pair(hex_literal: Pair<Rule>, _file: FileId) -> Result<Self, PestError> {
         assert!(hex_literal.as_rule() == Rule::hex_literal);
 
         if hex_literal.as_str().len() % 2 != 0 {
@@ -31,9 +33,41 @@ impl FromPair for RHexLiteral {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RDecimalLiteral(pub Bytes32);
+
+impl FromPair for RDecimalLiteral {
+    fn from_pair(decimal_literal: Pair<Rule>, _file: FileId) -> Result<Self, PestError> {
+        assert!(decimal_literal.as_rule() == Rule::decimal_literal);
+
+        let ten = Bytes32::from_usize(10);
+        let mut value = Bytes32::default();
+        for digit in decimal_literal.as_str().bytes() {
+            let Some(next) = value
+                .checked_mul(&ten)
+                .and_then(|x| x.checked_add(&Bytes32::from_usize((digit - b'0') as usize)))
+            else {
+                return Err(new_error_from_pair(
+                    &decimal_literal,
+                    "Decimal literal exceeds 256 bits.".to_owned(),
+                ));
+            };
+            value = next;
+        }
+
+        Ok(Self(value))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct RStringLiteral(pub String);
 
+impl RStringLiteral {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
 impl From<String> for RStringLiteral {
     fn from(value: String) -> Self {
         Self(value)
@@ -41,12 +75,49 @@ impl From<String> for RStringLiteral {
 }
 
 impl FromPair for RStringLiteral {
-    fn from_pair(string_literal: Pair<Rule>) -> Result<Self, PestError> {
+    fn from_pair(string_literal: Pair<Rule>, _file: FileId) -> Result<Self, PestError> {
         assert!(string_literal.as_rule() == Rule::string_literal);
 
-        Ok(map_unique_child(string_literal, |string_inner| {
+        map_unique_child(string_literal, |string_inner| {
             assert!(string_inner.as_rule() == Rule::string_inner);
-            string_inner.as_str().to_owned().into()
-        }))
+            unescape(string_inner.as_str())
+                .map(Into::into)
+                .ok_or_else(|| {
+                    new_error_from_pair(&string_inner, "Invalid unicode escape.".to_owned())
+                })
+        })
+    }
+}
+
+/// Replaces the escape sequences of a string, already checked by the grammar. Returns `None` for a
+/// `\u{..}` that is not a unicode scalar value.
+fn unescape(string: &str) -> Option<String> {
+    let mut res = String::with_capacity(string.len());
+    let mut chars = string.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            res.push(c);
+            continue;
+        }
+        res.push(match chars.next().unwrap() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            'x' => {
+                let code = chars.by_ref().take(2).collect::<String>();
+                char::from(u8::from_str_radix(&code, 16).unwrap())
+            }
+            'u' => {
+                let code = chars
+                    .by_ref()
+                    .skip(1)
+                    .take_while(|c| *c != '}')
+                    .collect::<String>();
+                char::from_u32(u32::from_str_radix(&code, 16).unwrap())?
+            }
+            escaped => escaped,
+        });
     }
+    Some(res)
 }
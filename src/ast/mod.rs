@@ -2,8 +2,12 @@ mod attribute;
 mod block;
 mod constant;
 mod contract;
+mod expression;
 mod file;
 mod function;
+mod immutable;
+mod import;
+mod link;
 mod literal;
 mod variable;
 
@@ -11,7 +15,11 @@ pub use attribute::*;
 pub use block::*;
 pub use constant::*;
 pub use contract::*;
+pub use expression::*;
 pub use file::*;
 pub use function::*;
+pub use immutable::*;
+pub use import::*;
+pub use link::*;
 pub use literal::*;
 pub use variable::*;
@@ -1,17 +1,18 @@
 use pest::iterators::Pair;
 use quick_impl::quick_impl_all;
 
-use super::RHexLiteral;
+use super::{RHexLiteral, RStringLiteral};
 use crate::parser::{
     error::PestError,
     parser::{FromPair, Located, Rule, get_next, map_unique_child},
+    source::FileId,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct RVariable(pub String);
 
 impl FromPair for RVariable {
-    fn from_pair(variable: Pair<Rule>) -> Result<Self, PestError> {
+    fn from_pair(variable: Pair<Rule>, _file: FileId) -> Result<Self, PestError> {
         assert!(variable.as_rule() == Rule::variable);
 
         Ok(RVariable(variable.as_str().to_owned()))
@@ -27,24 +28,34 @@ impl RVariable {
 #[derive(Debug, Clone)]
 pub struct RVariableWithField {
     pub variable: Located<RVariable>,
+    /// Fields between the variable and the last field, e.g. `loop` in `block.loop.pc`.
+    pub path: Vec<Located<RVariable>>,
     pub field: Located<RVariable>,
 }
 
 impl FromPair for RVariableWithField {
-    fn from_pair(variable_with_field: Pair<Rule>) -> Result<Self, PestError> {
+    fn from_pair(variable_with_field: Pair<Rule>, file: FileId) -> Result<Self, PestError> {
         assert!(variable_with_field.as_rule() == Rule::variable_with_field);
 
         let mut inner = variable_with_field.into_inner();
 
-        let variable = Located::<RVariable>::from_pair(get_next(&mut inner, Rule::variable))?;
+        let variable = Located::<RVariable>::from_pair(get_next(&mut inner, Rule::variable), file)?;
 
-        let _ = get_next(&mut inner, Rule::dot);
-
-        let field = Located::<RVariable>::from_pair(get_next(&mut inner, Rule::variable))?;
-
-        assert!(inner.next().is_none());
+        let mut path = Vec::new();
+        while let Some(dot) = inner.next() {
+            assert!(dot.as_rule() == Rule::dot);
+            path.push(Located::<RVariable>::from_pair(
+                get_next(&mut inner, Rule::variable),
+                file,
+            )?);
+        }
+        let field = path.pop().unwrap();
 
-        Ok(Self { variable, field })
+        Ok(Self {
+            variable,
+            path,
+            field,
+        })
     }
 }
 
@@ -52,15 +63,17 @@ impl FromPair for RVariableWithField {
 pub struct RCompileVariable(pub Located<RVariable>);
 
 impl FromPair for RCompileVariable {
-    fn from_pair(compile_variable: Pair<Rule>) -> Result<Self, PestError> {
+    fn from_pair(compile_variable: Pair<Rule>, file: FileId) -> Result<Self, PestError> {
         assert!(compile_variable.as_rule() == Rule::compile_variable);
 
         let mut compile_var_inner = compile_variable.into_inner();
 
         _ = get_next(&mut compile_var_inner, Rule::dol);
 
-        let res =
-            Located::<RVariable>::from_pair(get_next(&mut compile_var_inner, Rule::variable))?;
+        let res = Located::<RVariable>::from_pair(
+            get_next(&mut compile_var_inner, Rule::variable),
+            file,
+        )?;
 
         _ = get_next(&mut compile_var_inner, Rule::dol);
 
@@ -78,46 +91,21 @@ impl RCompileVariable {
 #[quick_impl_all(impl From)]
 pub enum RHexAlias {
     HexLiteral(RHexLiteral),
+    StringLiteral(RStringLiteral),
     Variable(RVariable),
     CompileVariable(RCompileVariable),
 }
 
 impl FromPair for RHexAlias {
-    fn from_pair(hex_alias: Pair<Rule>) -> Result<Self, PestError> {
+    fn from_pair(hex_alias: Pair<Rule>, file: FileId) -> Result<Self, PestError> {
         assert!(hex_alias.as_rule() == Rule::hex_alias);
 
         map_unique_child(hex_alias, |child| match child.as_rule() {
-            Rule::hex_literal => Ok(RHexLiteral::from_pair(child)?.into()),
-            Rule::variable => Ok(RVariable::from_pair(child)?.into()),
-            Rule::compile_variable => Ok(RCompileVariable::from_pair(child)?.into()),
+            Rule::hex_literal => Ok(RHexLiteral::from_pair(child, file)?.into()),
+            Rule::string_literal => Ok(RStringLiteral::from_pair(child, file)?.into()),
+            Rule::variable => Ok(RVariable::from_pair(child, file)?.into()),
+            Rule::compile_variable => Ok(RCompileVariable::from_pair(child, file)?.into()),
             _ => unreachable!(),
         })
     }
 }
-
-#[derive(Debug, Clone)]
-pub struct RConcatenation(pub Vec<Located<RHexAlias>>);
-
-impl FromPair for RConcatenation {
-    fn from_pair(variables_concat: Pair<Rule>) -> Result<Self, PestError> {
-        assert!(variables_concat.as_rule() == Rule::concatenation);
-
-        let mut variables_concat_inner = variables_concat.into_inner();
-
-        let mut res = vec![Located::<RHexAlias>::from_pair(get_next(
-            &mut variables_concat_inner,
-            Rule::hex_alias,
-        ))?];
-
-        while let Some(at) = variables_concat_inner.next() {
-            assert!(at.as_rule() == Rule::at);
-
-            res.push(Located::<RHexAlias>::from_pair(get_next(
-                &mut variables_concat_inner,
-                Rule::hex_alias,
-            ))?)
-        }
-
-        Ok(Self(res))
-    }
-}
@@ -1,10 +1,13 @@
 use std::collections::HashMap;
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use serde::{Deserialize, Serialize};
 
 use super::settings::{deserialize_bytes, serialize_bytes};
 
+/// Number of bytes of a linked address.
+pub const ADDRESS_LENGTH: usize = 20;
+
 #[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(deny_unknown_fields, rename_all = "camelCase")]
 pub struct Artifacts {
@@ -21,6 +24,27 @@ pub struct ContractArtifacts {
         deserialize_with = "deserialize_bytes"
     )]
     pub bytecode: Bytes,
+    /// Deployment code of the contract, returning its bytecode.
+    #[serde(
+        default,
+        serialize_with = "serialize_bytes",
+        deserialize_with = "deserialize_bytes"
+    )]
+    pub initcode: Bytes,
+    /// Offsets of the bytes reserved for each immutable in the bytecode.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub immutable_references: HashMap<String, Vec<usize>>,
+    /// Positions of the addresses of each library not linked yet, including the ones of the
+    /// contracts whose code is copied.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub link_references: HashMap<String, Vec<LinkReference>>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct LinkReference {
+    pub offset: usize,
+    pub length: usize,
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -28,6 +52,8 @@ pub struct ContractArtifacts {
 pub struct BlockArtifacts {
     pub pc: usize,
     pub size: usize,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, usize>,
 }
 
 impl Artifacts {
@@ -38,20 +64,136 @@ impl Artifacts {
     pub fn main_artifacts(&self) -> &ContractArtifacts {
         self.contracts.get(&self.main).unwrap()
     }
+
+    /// Writes the address of the library `name` in the bytecode of every contract referencing it.
+    /// Fails if no contract references a library `name` not linked yet.
+    pub fn link(&mut self, name: &str, address: &[u8; ADDRESS_LENGTH]) -> Result<(), String> {
+        let mut linked = false;
+        for contract_artifacts in self.contracts.values_mut() {
+            linked |= contract_artifacts.link(name, address);
+        }
+        if !linked {
+            return Err(format!("No contract references the library `{}`", name));
+        }
+        Ok(())
+    }
 }
 
 impl ContractArtifacts {
     pub fn set_pc(&mut self, block_name: &str, pc: usize) {
         assert!(
             self.blocks
-                .insert(block_name.to_owned(), BlockArtifacts { pc, size: 0 })
+                .insert(
+                    block_name.to_owned(),
+                    BlockArtifacts {
+                        pc,
+                        ..Default::default()
+                    }
+                )
                 .is_none()
         );
     }
 
+    pub fn set_label(&mut self, block_name: &str, label_name: &str, pc: usize) {
+        let ba = self.blocks.get_mut(block_name).unwrap();
+        assert!(ba.labels.insert(label_name.to_owned(), pc).is_none());
+    }
+
+    pub fn add_immutable_reference(&mut self, name: &str, offset: usize) {
+        self.immutable_references
+            .entry(name.to_owned())
+            .or_default()
+            .push(offset);
+    }
+
+    pub fn add_link_reference(&mut self, name: &str, offset: usize) {
+        self.link_references
+            .entry(name.to_owned())
+            .or_default()
+            .push(LinkReference {
+                offset,
+                length: ADDRESS_LENGTH,
+            });
+    }
+
+    /// Returns whether the contract references the library `name`.
+    fn link(&mut self, name: &str, address: &[u8; ADDRESS_LENGTH]) -> bool {
+        let Some(link_references) = self.link_references.remove(name) else {
+            return false;
+        };
+        let mut bytecode = BytesMut::from(&self.bytecode[..]);
+        for link_reference in &link_references {
+            let offset = link_reference.offset;
+            bytecode[offset..offset + link_reference.length].copy_from_slice(address);
+        }
+        self.bytecode = bytecode.freeze();
+
+        // the bytecode is at the end of the deployment code, if any
+        if self.initcode.is_empty() {
+            return true;
+        }
+        let initcode_offset = self.initcode.len() - self.bytecode.len();
+        let mut initcode = BytesMut::from(&self.initcode[..]);
+        for link_reference in &link_references {
+            let offset = initcode_offset + link_reference.offset;
+            initcode[offset..offset + link_reference.length].copy_from_slice(address);
+        }
+        self.initcode = initcode.freeze();
+        true
+    }
+
     pub fn set_size(&mut self, block_name: &str, end: usize) {
         let ba = self.blocks.get_mut(block_name).unwrap();
         assert!(end >= ba.pc);
         ba.size = end - ba.pc;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link() {
+        let bytecode = "73000000000000000000000000000000000000000031";
+        let mut contract_artifacts = ContractArtifacts {
+            bytecode: Bytes::from(hex::decode(bytecode).unwrap()),
+            initcode: Bytes::from(hex::decode(format!("60168060095f395ff3{}", bytecode)).unwrap()),
+            ..Default::default()
+        };
+        contract_artifacts.add_link_reference("Helper", 1);
+        let mut artifacts = Artifacts {
+            main: "Main".to_owned(),
+            contracts: HashMap::from([("Main".to_owned(), contract_artifacts)]),
+        };
+
+        artifacts.link("Helper", &[0x11; ADDRESS_LENGTH]).unwrap();
+        assert_eq!(
+            hex::encode(artifacts.main_bytecode()),
+            "73111111111111111111111111111111111111111131"
+        );
+        assert_eq!(
+            hex::encode(&artifacts.main_artifacts().initcode),
+            "60168060095f395ff373111111111111111111111111111111111111111131"
+        );
+        assert!(artifacts.main_artifacts().link_references.is_empty());
+
+        // already linked
+        let err = artifacts
+            .link("Helper", &[0x22; ADDRESS_LENGTH])
+            .unwrap_err();
+        assert_eq!(err, "No contract references the library `Helper`");
+    }
+
+    #[test]
+    fn test_link_unknown_library() {
+        let mut artifacts = Artifacts {
+            main: "Main".to_owned(),
+            contracts: HashMap::from([("Main".to_owned(), ContractArtifacts::default())]),
+        };
+        let err = artifacts
+            .link("Unknown", &[0x11; ADDRESS_LENGTH])
+            .unwrap_err();
+        assert_eq!(err, "No contract references the library `Unknown`");
+    }
+}
@@ -1,44 +1,73 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 
-use bytes::{BufMut, Bytes, BytesMut};
+use bytes::{BufMut, BytesMut};
 
-use super::artifacts::ContractArtifacts;
+use super::artifacts::{ADDRESS_LENGTH, ContractArtifacts};
 use super::fillers::{fill_with_pattern, fill_with_random};
+use super::initcode::initcode;
+use super::optimizer::{push_constant, synthesize};
 use super::settings::{CompilerSettings, FillingPatern};
 use crate::compile::artifacts::Artifacts;
-use crate::pre_processing::opcode::{PUSH0, PUSH1, PUSH2, PUSH32, push_length};
-use crate::pre_processing::pre_processing::{Block, BlockItemInner, Contract, PushInner};
+use crate::parser::error::{PestError, new_error_from_location, new_generic_error};
+use crate::parser::parser::{Located, Location};
+use crate::parser::source::Sources;
+use crate::pre_processing::expression::Expression;
+use crate::pre_processing::opcode::{CODESIZE, JUMP, JUMPDEST, JUMPI, PC, PUSH0, push_length};
+use crate::pre_processing::pre_processing::{Block, BlockItemInner, Contract, Push, PushInner};
 use crate::types::bytes32::Bytes32;
 
-pub fn compile_contracts(contracts: Vec<Contract>, settings: CompilerSettings) -> Artifacts {
+pub fn compile_contracts(
+    input: &Sources,
+    contracts: Vec<Contract>,
+    settings: CompilerSettings,
+) -> Result<Artifacts, PestError> {
     let mut artifacts = Artifacts {
         main: contracts[0].name.clone(),
         ..Default::default()
     };
 
-    let mut bytecodes = HashMap::<usize, Bytes>::new();
+    let mut compiled = HashMap::<usize, ContractArtifacts>::new();
 
     for contract_index in (0..contracts.len()).rev() {
-        let contract_artifacts =
-            compile_contract(&contracts[contract_index].blocks, &bytecodes, &settings);
-        let contract_name = &contracts[contract_index].name;
-
-        artifacts
-            .contracts
-            .insert(contract_name.clone(), contract_artifacts);
+        let contract_settings = CompilerSettings {
+            evm_version: contracts[contract_index].evm_version,
+            ..settings.clone()
+        };
+        let mut contract_artifacts = compile_contract(
+            input,
+            &contracts[contract_index],
+            &compiled,
+            &contract_settings,
+        )?;
+        contract_artifacts.initcode = initcode(
+            &contract_artifacts.bytecode,
+            contract_settings.use_push0(),
+            contracts[contract_index].constructor_args,
+        );
+        check_assertions(input, &contracts[contract_index], &contract_artifacts)?;
+        // unreferenced immutables and links are reported without offsets
+        for immutable_name in &contracts[contract_index].immutables {
+            contract_artifacts
+                .immutable_references
+                .entry(immutable_name.clone())
+                .or_default();
+        }
+        for link_name in &contracts[contract_index].links {
+            contract_artifacts
+                .link_references
+                .entry(link_name.clone())
+                .or_default();
+        }
 
-        bytecodes.insert(
-            contract_index,
-            artifacts
-                .contracts
-                .get(contract_name)
-                .unwrap()
-                .bytecode
-                .clone(),
+        artifacts.contracts.insert(
+            contracts[contract_index].name.clone(),
+            contract_artifacts.clone(),
         );
+        compiled.insert(contract_index, contract_artifacts);
     }
 
-    artifacts
+    Ok(artifacts)
 }
 
 #[derive(Clone, Debug)]
@@ -56,22 +85,266 @@ struct SizeHole {
     pub hole_pos: usize,
 }
 
+#[derive(Clone, Debug)]
+struct ExpressionHole {
+    pub expression: Located<Expression<PushInner>>,
+    pub hole_pos: usize,
+}
+
 #[derive(Clone, Debug)]
 enum Hole {
     Pc(PcHole),
     Size(SizeHole),
+    Expression(ExpressionHole),
+}
+
+impl Hole {
+    fn new(push: &PushInner, hole_pos: usize) -> Self {
+        match push {
+            PushInner::BlockSize { index, start, end } => Self::Size(SizeHole {
+                block_index: *index,
+                line_start: *start,
+                line_end: *end,
+                hole_pos,
+            }),
+            PushInner::BlockPc { index, line } => Self::Pc(PcHole {
+                block_index: *index,
+                line: *line,
+                hole_pos,
+            }),
+            PushInner::Expression(expression) => Self::Expression(ExpressionHole {
+                expression: expression.as_ref().clone(),
+                hole_pos,
+            }),
+            _ => unreachable!(),
+        }
+    }
+
+    fn hole_pos(&self) -> usize {
+        match self {
+            Self::Pc(pc_hole) => pc_hole.hole_pos,
+            Self::Size(size_hole) => size_hole.hole_pos,
+            Self::Expression(expression_hole) => expression_hole.hole_pos,
+        }
+    }
+}
+
+/// Maximum number of bytes pushed for a block pc or size.
+const MAX_OFFSET_WIDTH: usize = 3;
+
+/// Number of bytes of each offset pushed by `Contract.immutable.NAME.offsets`.
+const IMMUTABLE_OFFSET_WIDTH: usize = 2;
+
+/// A constant push that can be replaced by the opcode `pc` or `codesize` if its value matches.
+#[derive(Clone, Debug)]
+struct Candidate {
+    pub pos: usize,
+    /// Number of bytes of the push in the current layout.
+    pub len: usize,
+    pub value: Bytes32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Substitution {
+    Untried,
+    Pc,
+    Codesize,
+    /// The substitution was invalidated by another change of the layout, it is not tried again.
+    Banned,
+}
+
+/// Table entry or push of explicit width depending on the layout, written on a fixed width.
+#[derive(Clone, Debug)]
+struct EntryHole {
+    pub hole: Hole,
+    pub width: usize,
+    pub location: Location,
+}
+
+/// The bytecode of a contract laid out with given hole widths and substitutions, the holes being
+/// filled with zeros.
+struct Layout {
+    code: BytesMut,
+    artifacts: ContractArtifacts,
+    block_positions: HashMap<usize, Vec<usize>>,
+    holes: Vec<Hole>,
+    candidates: Vec<Candidate>,
+    entries: Vec<EntryHole>,
+    /// Number of bytes completing the last push started by the entries of each table, in order.
+    table_paddings: Vec<usize>,
+    /// Location of the pushes, by pc.
+    push_locations: HashMap<usize, Location>,
+    /// Bytes of the code never executed: data blocks, table entries and codes of other contracts.
+    data_ranges: Vec<Range<usize>>,
 }
 
 fn compile_contract(
+    input: &Sources,
+    contract: &Contract,
+    compiled: &HashMap<usize, ContractArtifacts>,
+    settings: &CompilerSettings,
+) -> Result<ContractArtifacts, PestError> {
+    // The width of every hole starts at its minimum, and is increased until the values fit. A
+    // constant push is replaced by `pc` or `codesize` when its value matches, and restored for good
+    // if a later change of the layout breaks the match. Widths never shrink and substitutions are
+    // tried at most once, so that the layout always reaches a fixed point. The table entries are
+    // written with their values in the previous layout, as their bytes can change the padding of
+    // their block: like the widths, the padding after a table never shrinks.
+    let min_width = if settings.use_push0() { 0 } else { 1 };
+    let mut hole_widths = Vec::<usize>::new();
+    let mut substitutions = Vec::<Substitution>::new();
+    let mut entry_values = Vec::<Bytes32>::new();
+    let mut table_paddings = Vec::<usize>::new();
+    loop {
+        let mut layout = lay_out(
+            input,
+            &contract.blocks,
+            compiled,
+            settings,
+            &hole_widths,
+            &substitutions,
+            &entry_values,
+            &table_paddings,
+        )?;
+        // the first layout is made before the number of holes and candidates is known
+        let mut fixed_point = hole_widths.len() == layout.holes.len()
+            && substitutions.len() == layout.candidates.len();
+
+        table_paddings.resize(layout.table_paddings.len(), 0);
+        for (padding, required) in table_paddings.iter_mut().zip(&layout.table_paddings) {
+            if required > padding {
+                *padding = *required;
+                fixed_point = false;
+            }
+        }
+
+        let mut new_entry_values = Vec::with_capacity(layout.entries.len());
+        for entry in &layout.entries {
+            // a negative value is an error once the layout is final, it can change until then
+            new_entry_values
+                .push(hole_value(input, &layout.block_positions, &entry.hole)?.unwrap_or_default());
+        }
+        if new_entry_values != entry_values {
+            entry_values = new_entry_values;
+            fixed_point = false;
+        }
+        hole_widths.resize(layout.holes.len(), min_width);
+        substitutions.resize(layout.candidates.len(), Substitution::Untried);
+
+        let mut values = Vec::with_capacity(layout.holes.len());
+        for (hole, width) in layout.holes.iter().zip(hole_widths.iter_mut()) {
+            let value = hole_value(input, &layout.block_positions, hole)?.unwrap_or_default();
+            let required = value.right_content().len().max(min_width);
+            if required > *width {
+                if !matches!(hole, Hole::Expression(_)) && required > MAX_OFFSET_WIDTH {
+                    return Err(new_generic_error(format!(
+                        "The bytecode is too long, block offsets must fit in {} bytes.",
+                        MAX_OFFSET_WIDTH
+                    )));
+                }
+                *width = required;
+                fixed_point = false;
+            }
+            values.push(value);
+        }
+
+        let codesize = layout.code.len();
+        for (candidate, substitution) in layout.candidates.iter().zip(substitutions.iter_mut()) {
+            let valid = match substitution {
+                Substitution::Pc => candidate.value == Bytes32::from_usize(candidate.pos),
+                Substitution::Codesize => candidate.value == Bytes32::from_usize(codesize),
+                _ => true,
+            };
+            if !valid {
+                *substitution = Substitution::Banned;
+                fixed_point = false;
+            }
+        }
+
+        // A substitution shifts the code after it, so a single one is made once the rest of the
+        // layout is stable, the first in the bytecode. `pc` is tried before `codesize`, which is
+        // invalidated by any other substitution. Substituting a push by `codesize` shrinks the
+        // code, so its value is compared to the size after the substitution. The code size is not
+        // known when the constructor arguments are kept after the code.
+        if fixed_point {
+            let untried = || {
+                layout
+                    .candidates
+                    .iter()
+                    .zip(&substitutions)
+                    .enumerate()
+                    .filter(|(_, (_, substitution))| **substitution == Substitution::Untried)
+                    .map(|(index, (candidate, _))| (index, candidate))
+            };
+            let substitution = untried()
+                .find(|(_, candidate)| candidate.value == Bytes32::from_usize(candidate.pos))
+                .map(|(index, _)| (index, Substitution::Pc))
+                .or_else(|| {
+                    if contract.constructor_args {
+                        return None;
+                    }
+                    untried()
+                        .find(|(_, candidate)| {
+                            candidate.value == Bytes32::from_usize(codesize + 1 - candidate.len)
+                        })
+                        .map(|(index, _)| (index, Substitution::Codesize))
+                });
+            if let Some((index, substitution)) = substitution {
+                substitutions[index] = substitution;
+                fixed_point = false;
+            }
+        }
+
+        if fixed_point {
+            for (entry, value) in layout.entries.iter().zip(&entry_values) {
+                check_hole_sign(input, &layout.block_positions, &entry.hole)?;
+                check_entry_width(input, value, entry.width, &entry.location)?;
+            }
+            for ((hole, width), value) in layout.holes.iter().zip(&hole_widths).zip(&values) {
+                check_hole_sign(input, &layout.block_positions, hole)?;
+                let hole_pos = hole.hole_pos();
+                layout.code[hole_pos..hole_pos + width].copy_from_slice(&value[32 - width..]);
+            }
+            check_jump_targets(input, contract, &layout)?;
+            warn_data_jumpdests(contract, &layout);
+            layout.artifacts.bytecode = layout.code.into();
+            return Ok(layout.artifacts);
+        }
+    }
+}
+
+/// Lays out the blocks, pushing the `i`-th hole met with `hole_widths[i]` bytes (1 by default),
+/// applying `substitutions[i]` to the `i`-th candidate met (none by default), writing
+/// `entry_values[i]` in the `i`-th table entry or explicit width push depending on the layout (zero by default),
+/// and padding the `i`-th table with `table_paddings[i]` bytes (none by default).
+#[allow(clippy::too_many_arguments)]
+fn lay_out(
+    input: &Sources,
     blocks: &[Block],
-    bytecodes: &HashMap<usize, Bytes>,
+    compiled: &HashMap<usize, ContractArtifacts>,
     settings: &CompilerSettings,
-) -> ContractArtifacts {
+    hole_widths: &[usize],
+    substitutions: &[Substitution],
+    entry_values: &[Bytes32],
+    table_paddings: &[usize],
+) -> Result<Layout, PestError> {
     let mut contract_artifacts = ContractArtifacts::default();
     let mut res = BytesMut::new();
 
     let mut block_positions = HashMap::<usize, Vec<usize>>::new();
     let mut holes = Vec::<Hole>::new();
+    let mut candidates = Vec::<Candidate>::new();
+    let mut entries = Vec::<EntryHole>::new();
+    let mut required_table_paddings = Vec::<usize>::new();
+    let mut push_locations = HashMap::<usize, Location>::new();
+    let mut data_ranges = Vec::<Range<usize>>::new();
+    let put_hole = |res: &mut BytesMut, hole_index: usize| {
+        let width = hole_widths.get(hole_index).copied().unwrap_or(1);
+        res.put_u8(PUSH0 + width as u8);
+        let hole_pos = res.len();
+        res.put_bytes(0x00, width);
+        hole_pos
+    };
     let blocks_len = blocks.len();
     for (block_index, block) in blocks.iter().enumerate() {
         contract_artifacts.set_pc(&block.name, res.len());
@@ -85,10 +358,82 @@ fn compile_contract(
             pcs.push(res.len());
             match &item.inner {
                 BlockItemInner::Bytes(bytes) => res.extend_from_slice(bytes),
-                BlockItemInner::Contract(contract_index) => {
-                    res.extend_from_slice(bytecodes.get(contract_index).unwrap())
+                BlockItemInner::Contract(contract_index)
+                | BlockItemInner::Initcode(contract_index) => {
+                    let copied_artifacts = compiled.get(contract_index).unwrap();
+                    let copied = if matches!(item.inner, BlockItemInner::Contract(_)) {
+                        &copied_artifacts.bytecode
+                    } else {
+                        &copied_artifacts.initcode
+                    };
+                    // the bytecode is at the end of the deployment code
+                    let bytecode_pos = res.len() + copied.len() - copied_artifacts.bytecode.len();
+                    for (link_name, link_references) in &copied_artifacts.link_references {
+                        for link_reference in link_references {
+                            contract_artifacts.add_link_reference(
+                                link_name,
+                                bytecode_pos + link_reference.offset,
+                            );
+                        }
+                    }
+                    res.extend_from_slice(copied);
+                    data_ranges.push(pcs.last().copied().unwrap()..res.len());
+                }
+                BlockItemInner::TableEntry(entry) => match &entry.inner {
+                    PushInner::Constant(_)
+                    | PushInner::InitcodeSize { .. }
+                    | PushInner::ImmutableOffsets { .. } => {
+                        let value = constant_value(input, compiled, &entry.inner)?;
+                        check_entry_width(input, &value, entry.width, &entry.location)?;
+                        res.extend_from_slice(&value[32 - entry.width..]);
+                    }
+                    PushInner::BlockSize { .. }
+                    | PushInner::BlockPc { .. }
+                    | PushInner::Expression(_) => {
+                        let value = entry_values.get(entries.len()).cloned().unwrap_or_default();
+                        entries.push(EntryHole {
+                            hole: Hole::new(&entry.inner, res.len()),
+                            width: entry.width,
+                            location: entry.location.clone(),
+                        });
+                        res.extend_from_slice(&value[32 - entry.width..]);
+                    }
+                    _ => unreachable!(),
+                },
+                BlockItemInner::Push(Push {
+                    inner,
+                    width: Some(width),
+                    location,
+                    ..
+                }) => {
+                    // an explicit width disables the optimizations of the push
+                    push_locations.insert(res.len(), location.clone());
+                    res.put_u8(PUSH0 + width.inner as u8);
+                    match inner {
+                        PushInner::Constant(_)
+                        | PushInner::InitcodeSize { .. }
+                        | PushInner::ImmutableOffsets { .. } => {
+                            let value = constant_value(input, compiled, inner)?;
+                            check_entry_width(input, &value, width.inner, &width.location)?;
+                            res.extend_from_slice(&value[32 - width.inner..]);
+                        }
+                        PushInner::BlockSize { .. }
+                        | PushInner::BlockPc { .. }
+                        | PushInner::Expression(_) => {
+                            let value =
+                                entry_values.get(entries.len()).cloned().unwrap_or_default();
+                            entries.push(EntryHole {
+                                hole: Hole::new(inner, res.len()),
+                                width: width.inner,
+                                location: width.location.clone(),
+                            });
+                            res.extend_from_slice(&value[32 - width.inner..]);
+                        }
+                        _ => unreachable!(),
+                    }
                 }
                 BlockItemInner::Push(push) => {
+                    push_locations.insert(res.len(), push.location.clone());
                     let assumes: HashMap<Bytes32, u8> = if push.attributes.optimization {
                         push.attributes
                             .assumes
@@ -99,68 +444,76 @@ fn compile_contract(
                         HashMap::new()
                     };
                     match &push.inner {
-                        PushInner::Constant(cst) => {
-                            if settings.push0 && cst.is_zero() {
-                                res.put_u8(PUSH0);
-                            } else if let Some(op) = assumes.get(cst) {
-                                res.put_u8(*op);
-                            } else if !settings.push0 && cst.is_zero() {
-                                res.put_u8(PUSH1);
-                                res.put_u8(0x00);
+                        PushInner::Constant(_)
+                        | PushInner::InitcodeSize { .. }
+                        | PushInner::ImmutableOffsets { .. } => {
+                            let cst = &constant_value(input, compiled, &push.inner)?;
+                            let code = if push.attributes.optimization {
+                                synthesize(cst, &assumes, settings)
                             } else {
-                                let content = cst.right_content();
-                                let push = PUSH0 + (content.len() as u8);
-                                assert!(PUSH0 < push && push <= PUSH32);
-                                res.put_u8(push);
-                                res.extend_from_slice(content);
+                                push_constant(cst, settings.use_push0())
+                            };
+                            if push.attributes.optimization && code.len() > 1 {
+                                let pos = res.len();
+                                match substitutions.get(candidates.len()) {
+                                    Some(Substitution::Pc) => res.put_u8(PC),
+                                    Some(Substitution::Codesize) => res.put_u8(CODESIZE),
+                                    _ => res.extend_from_slice(&code),
+                                }
+                                candidates.push(Candidate {
+                                    pos,
+                                    len: res.len() - pos,
+                                    value: cst.clone(),
+                                });
+                            } else {
+                                res.extend_from_slice(&code);
                             }
                         }
-                        PushInner::BlockSize { index, start, end } => {
-                            res.put_u8(PUSH2);
-                            holes.push(Hole::Size(SizeHole {
-                                block_index: *index,
-                                line_start: *start,
-                                line_end: *end,
-                                hole_pos: res.len(),
-                            }));
-                            res.put_u8(0x00);
-                            res.put_u8(0x00);
+                        PushInner::BlockSize { .. }
+                        | PushInner::BlockPc { .. }
+                        | PushInner::Expression(_) => {
+                            let hole_pos = put_hole(&mut res, holes.len());
+                            holes.push(Hole::new(&push.inner, hole_pos));
+                        }
+                        PushInner::Immutable { name, width } => {
+                            res.put_u8(PUSH0 + *width as u8);
+                            contract_artifacts.add_immutable_reference(name, res.len());
+                            res.put_bytes(0x00, *width);
                         }
-                        PushInner::BlockPc { index, line } => {
-                            res.put_u8(PUSH2);
-                            holes.push(Hole::Pc(PcHole {
-                                block_index: *index,
-                                line: *line,
-                                hole_pos: res.len(),
-                            }));
-                            res.put_u8(0x00);
-                            res.put_u8(0x00);
+                        PushInner::Link(name) => {
+                            res.put_u8(PUSH0 + ADDRESS_LENGTH as u8);
+                            contract_artifacts.add_link_reference(name, res.len());
+                            res.put_bytes(0x00, ADDRESS_LENGTH);
                         }
+                        PushInner::LabelPc { .. } => unreachable!(),
                     }
                 }
             }
 
+            if matches!(item.inner, BlockItemInner::TableEntry(_)) {
+                data_ranges.push(pcs.last().copied().unwrap()..res.len());
+            }
+
             for start_name in &item.start_names {
                 contract_artifacts.set_size(start_name, res.len());
             }
         }
         pcs.push(res.len());
+        if block.data {
+            data_ranges.push(pcs[0]..res.len());
+        }
 
-        if block_index != blocks_len - 1 {
-            let mut block_bytes_iter = res[pcs[0]..*pcs.last().unwrap()].iter();
-
-            let mut bytes_to_add = 0;
-            while let Some(op) = block_bytes_iter.next() {
-                if let Some(mut remaining_push) = push_length(*op) {
-                    while remaining_push > 0 {
-                        if block_bytes_iter.next().is_some() {
-                            remaining_push -= 1;
-                        } else {
-                            bytes_to_add = remaining_push;
-                            break;
-                        }
-                    }
-                }
+        if block_index != blocks_len - 1 && !block.data {
+            let mut bytes_to_add = unfinished_push_length(&res[pcs[0]..*pcs.last().unwrap()]);
+            if block.table {
+                // the entries of a table depend on the layout, so the padding after them is the
+                // largest one required so far
+                let padding = table_paddings
+                    .get(required_table_paddings.len())
+                    .copied()
+                    .unwrap_or(0);
+                required_table_paddings.push(bytes_to_add);
+                bytes_to_add = bytes_to_add.max(padding);
             }
 
             match &settings.filling_pattern {
@@ -172,32 +525,645 @@ fn compile_contract(
         }
 
         contract_artifacts.set_size(&block.name, res.len());
+        for label in &block.labels {
+            contract_artifacts.set_label(&label.block_name, &label.name, pcs[label.line]);
+        }
         block_positions.insert(block_index, pcs);
     }
 
-    for hole in holes {
-        match hole {
-            Hole::Pc(pc_hole) => {
-                let positions = block_positions.get(&pc_hole.block_index).unwrap();
-                let pc = positions[pc_hole.line];
-                if pc > 0xffff {
-                    panic!("file too long");
+    Ok(Layout {
+        code: res,
+        artifacts: contract_artifacts,
+        block_positions,
+        holes,
+        candidates,
+        entries,
+        table_paddings: required_table_paddings,
+        push_locations,
+        data_ranges,
+    })
+}
+
+/// Number of bytes missing to the last push started in `code`, zero if it is complete.
+fn unfinished_push_length(code: &[u8]) -> usize {
+    let mut code_iter = code.iter();
+    while let Some(op) = code_iter.next() {
+        if let Some(mut remaining_push) = push_length(*op) {
+            while remaining_push > 0 {
+                if code_iter.next().is_some() {
+                    remaining_push -= 1;
+                } else {
+                    return remaining_push;
                 }
-                res[pc_hole.hole_pos + 1] = (pc % 256) as u8;
-                res[pc_hole.hole_pos] = (pc / 256) as u8;
-            }
-            Hole::Size(size_hole) => {
-                let positions = block_positions.get(&size_hole.block_index).unwrap();
-                let size = positions[size_hole.line_end] - positions[size_hole.line_start];
-                if size > 0xffff {
-                    panic!("file too long");
+            }
+        }
+    }
+    0
+}
+
+/// Checks that the values pushed right before a `jump` or a `jumpi` are valid jump destinations,
+/// skipping the data of the contract.
+fn check_jump_targets(
+    input: &Sources,
+    contract: &Contract,
+    layout: &Layout,
+) -> Result<(), PestError> {
+    let code = &layout.code;
+    let jumpdests = valid_jumpdests(code);
+    let mut data_ranges = layout.data_ranges.iter().peekable();
+    // pc of the previous push, and its value if it fits
+    let mut previous_push: Option<(usize, Option<usize>)> = None;
+    let mut pc = 0;
+    while pc < code.len() {
+        if let Some(range) = data_ranges.next_if(|range| range.start <= pc) {
+            previous_push = None;
+            pc = pc.max(range.end);
+            continue;
+        }
+
+        let op = code[pc];
+        if let Some(length) = push_length(op) {
+            let data = &code[pc + 1..(pc + 1 + length).min(code.len())];
+            let target = data.iter().try_fold(0usize, |target, byte| {
+                target.checked_mul(0x100)?.checked_add(*byte as usize)
+            });
+            previous_push = Some((pc, target));
+            pc += 1 + length;
+            continue;
+        }
+
+        // the raw bytes written in a block can be data, only the pushes of values are checked
+        if (op == JUMP || op == JUMPI)
+            && let Some((push_pc, target)) = previous_push
+            && let Some(location) = layout.push_locations.get(&push_pc)
+            && !target.is_some_and(|target| jumpdests.contains(&target))
+        {
+            let destination = match target {
+                Some(target) => jump_destination_name(contract, layout, target)
+                    .map(|name| format!("`{}` at pc {:#x}", name, target))
+                    .unwrap_or_else(|| format!("pc {:#x}", target)),
+                None => "a pc out of the code".to_owned(),
+            };
+            let message = format!(
+                "The jump at pc {:#x} goes to {}, which is not a `jumpdest`.",
+                pc, destination
+            );
+            return Err(new_error_from_location(input, location, &message));
+        }
+        previous_push = None;
+        pc += 1;
+    }
+    Ok(())
+}
+
+/// Name of the block or label starting at a pc, if any.
+fn jump_destination_name(contract: &Contract, layout: &Layout, pc: usize) -> Option<String> {
+    for (block_index, block) in contract.blocks.iter().enumerate() {
+        let pcs = layout.block_positions.get(&block_index).unwrap();
+        if pcs[0] == pc && pcs[0] != *pcs.last().unwrap() {
+            return Some(block.name.clone());
+        }
+        if let Some(label) = block.labels.iter().find(|label| pcs[label.line] == pc) {
+            return Some(format!("{}.{}", label.block_name, label.name));
+        }
+    }
+    None
+}
+
+/// Warns about the `0x5b` bytes of the data blocks that are valid jump destinations, as the code
+/// before them can jump there.
+fn warn_data_jumpdests(contract: &Contract, layout: &Layout) {
+    let jumpdests = valid_jumpdests(&layout.code);
+    for (block_index, block) in contract.blocks.iter().enumerate() {
+        if !block.data {
+            continue;
+        }
+        let pcs = layout.block_positions.get(&block_index).unwrap();
+        let block_jumpdests = (pcs[0]..*pcs.last().unwrap())
+            .filter(|pc| jumpdests.contains(pc))
+            .map(|pc| format!("{:#x}", pc))
+            .collect::<Vec<_>>();
+        if !block_jumpdests.is_empty() {
+            tracing::warn!(
+                "Data block `{}` of contract `{}` contains valid jump destinations at pc {}",
+                block.name,
+                contract.name,
+                block_jumpdests.join(", ")
+            );
+        }
+    }
+}
+
+/// Positions of the `jumpdest` opcodes, skipping the data of the pushes.
+fn valid_jumpdests(code: &[u8]) -> HashSet<usize> {
+    let mut jumpdests = HashSet::new();
+    let mut pc = 0;
+    while pc < code.len() {
+        if code[pc] == JUMPDEST {
+            jumpdests.insert(pc);
+        }
+        pc += 1 + push_length(code[pc]).unwrap_or(0);
+    }
+    jumpdests
+}
+
+fn check_entry_width(
+    input: &Sources,
+    value: &Bytes32,
+    width: usize,
+    location: &Location,
+) -> Result<(), PestError> {
+    if value.right_content().len() > width {
+        return Err(new_error_from_location(
+            input,
+            location,
+            &format!(
+                "The value 0x{} does not fit in {} byte(s).",
+                hex::encode(value.right_content()),
+                width
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Value of a push known before the layout. The offsets of the references to an immutable are
+/// concatenated on 2 bytes each, the last reference in the lowest bytes.
+fn constant_value(
+    input: &Sources,
+    compiled: &HashMap<usize, ContractArtifacts>,
+    push: &PushInner,
+) -> Result<Bytes32, PestError> {
+    let (contract_index, name, location) = match push {
+        PushInner::Constant(cst) => return Ok(cst.clone()),
+        PushInner::InitcodeSize { contract_index } => {
+            let initcode = &compiled.get(contract_index).unwrap().initcode;
+            return Ok(Bytes32::from_usize(initcode.len()));
+        }
+        PushInner::ImmutableOffsets {
+            contract_index,
+            name,
+            location,
+        } => (contract_index, name, location),
+        _ => unreachable!(),
+    };
+
+    let Some(offsets) = compiled
+        .get(contract_index)
+        .unwrap()
+        .immutable_references
+        .get(name)
+    else {
+        return Err(new_error_from_location(
+            input,
+            location,
+            &format!("Immutable `{}` not found.", name),
+        ));
+    };
+    if offsets.len() > 32 / IMMUTABLE_OFFSET_WIDTH {
+        return Err(new_error_from_location(
+            input,
+            location,
+            &format!(
+                "Immutable `{}` has more than {} references, their offsets do not fit in a push.",
+                name,
+                32 / IMMUTABLE_OFFSET_WIDTH
+            ),
+        ));
+    }
+
+    let mut packed = BytesMut::new();
+    for offset in offsets {
+        let Ok(offset) = u16::try_from(*offset) else {
+            return Err(new_error_from_location(
+                input,
+                location,
+                &format!(
+                    "The offsets of immutable `{}` do not fit in {} bytes.",
+                    name, IMMUTABLE_OFFSET_WIDTH
+                ),
+            ));
+        };
+        packed.put_u16(offset);
+    }
+    Ok(Bytes32::from_bytes(&packed.freeze(), true).unwrap())
+}
+
+/// Value of a hole in a given layout, `None` if its expression is negative in this layout.
+fn hole_value(
+    input: &Sources,
+    block_positions: &HashMap<usize, Vec<usize>>,
+    hole: &Hole,
+) -> Result<Option<Bytes32>, PestError> {
+    Ok(Some(match hole {
+        Hole::Pc(pc_hole) => {
+            Bytes32::from_usize(block_positions.get(&pc_hole.block_index).unwrap()[pc_hole.line])
+        }
+        Hole::Size(size_hole) => {
+            let positions = block_positions.get(&size_hole.block_index).unwrap();
+            Bytes32::from_usize(positions[size_hole.line_end] - positions[size_hole.line_start])
+        }
+        Hole::Expression(expression_hole) => {
+            let value = expression_hole
+                .expression
+                .clone()
+                .evaluate(input, &mut |leaf| {
+                    Bytes32::from_usize(block_offset(block_positions, leaf))
+                })?;
+            if value.negative {
+                return Ok(None);
+            }
+            Bytes32::from_bytes(&value.bytes, true).unwrap()
+        }
+    }))
+}
+
+/// Error if the expression of a hole is negative in the final layout.
+fn check_hole_sign(
+    input: &Sources,
+    block_positions: &HashMap<usize, Vec<usize>>,
+    hole: &Hole,
+) -> Result<(), PestError> {
+    match hole {
+        Hole::Expression(expression_hole)
+            if hole_value(input, block_positions, hole)?.is_none() =>
+        {
+            Err(new_error_from_location(
+                input,
+                &expression_hole.expression.location,
+                "The expression is negative once the contract is laid out.",
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn check_assertions(
+    input: &Sources,
+    contract: &Contract,
+    contract_artifacts: &ContractArtifacts,
+) -> Result<(), PestError> {
+    for layout_assertion in &contract.assertions {
+        let assertion = &layout_assertion.assertion;
+        let (actual, subject) = match &layout_assertion.block_name {
+            None => (
+                contract_artifacts.bytecode.len(),
+                format!("the code size of contract `{}`", contract.name),
+            ),
+            Some(block_name) => {
+                // unused blocks are not compiled
+                let Some(block_artifacts) = contract_artifacts.blocks.get(block_name) else {
+                    continue;
+                };
+                if assertion.subject.is_pc() {
+                    (
+                        block_artifacts.pc,
+                        format!("the pc of block `{}`", block_name),
+                    )
+                } else {
+                    (
+                        block_artifacts.size,
+                        format!("the size of block `{}`", block_name),
+                    )
                 }
-                res[size_hole.hole_pos + 1] = (size % 256) as u8;
-                res[size_hole.hole_pos] = (size / 256) as u8;
             }
+        };
+
+        if !assertion.comparison.holds(actual, assertion.value) {
+            return Err(new_error_from_location(
+                input,
+                &assertion.location,
+                &format!(
+                    "Assertion failed: {} is {:#x}, expected {} {:#x}.",
+                    subject,
+                    actual,
+                    assertion.comparison.as_str(),
+                    assertion.value
+                ),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Value of a block pc or size, once the blocks are laid out.
+fn block_offset(block_positions: &HashMap<usize, Vec<usize>>, push: &PushInner) -> usize {
+    match push {
+        PushInner::BlockPc { index, line } => block_positions.get(index).unwrap()[*line],
+        PushInner::BlockSize { index, start, end } => {
+            let positions = block_positions.get(index).unwrap();
+            positions[*end] - positions[*start]
         }
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compile::file::compile_source;
+
+    #[test]
+    fn test_block_offset_expressions() {
+        let artifacts = compile_source(
+            "contract C {
+                block main { push(end.pc + 0x20) push(end.pc - start.pc) push(a.size + b.size) stop }
+                #[keep]
+                block start { caller }
+                #[keep]
+                block a { 0x0102 }
+                #[keep]
+                block b { 0x030405 }
+                #[keep]
+                block end { stop }
+            }",
+            "C",
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(artifacts.main_bytecode()),
+            "602d600660050033010203040500"
+        );
+
+        // `end.pc` is 0x03 with a 1 byte hole, which makes the value 0x100
+        let artifacts = compile_source(
+            "contract C {
+                block main { push(end.pc + 0xfd) stop }
+                #[keep]
+                block end { stop }
+            }",
+            "C",
+        )
+        .unwrap();
+        assert_eq!(hex::encode(artifacts.main_bytecode()), "6101010000");
+    }
+
+    #[test]
+    fn test_negative_block_offset_expression() {
+        let err = compile_source(
+            "contract C {
+                block main { push(start.pc - end.pc) stop }
+                #[keep]
+                block start { caller }
+                #[keep]
+                block end { stop }
+            }",
+            "C",
+        )
+        .unwrap_err();
+        assert!(err.contains("main.mep:2:35"), "{}", err);
+        assert!(
+            err.contains("The expression is negative once the contract is laid out."),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_labels() {
+        let artifacts = compile_source(
+            "contract C {
+                block main {
+                    push(0x20)
+                    label loop_head:
+                    jumpdest
+                    push(0x01) swap1 sub
+                    dup1 push(loop_head.pc) jumpi
+                    push(end.done.pc) jump
+                }
+                block end { stop label done: jumpdest stop }
+            }",
+            "C",
+        )
+        .unwrap();
+        let contract = artifacts.main_artifacts();
+        assert_eq!(
+            hex::encode(&contract.bytecode),
+            "60205b6001900380600257600f56005b00"
+        );
+        assert_eq!(contract.blocks["main"].labels["loop_head"], 0x02);
+        assert_eq!(contract.blocks["end"].labels["done"], 0x0f);
+    }
+
+    #[test]
+    fn test_label_in_abstract_block_expanded_twice() {
+        let err = compile_source(
+            "contract C {
+                block main { &a &a stop }
+                abstract block a { label l: jumpdest }
+            }",
+            "C",
+        )
+        .unwrap_err();
+        assert!(
+            err.contains("Labels cannot be declared in an abstract block expanded more than once."),
+            "{}",
+            err
+        );
     }
 
-    contract_artifacts.bytecode = res.into();
-    contract_artifacts
+    #[test]
+    fn test_assertions() {
+        let source = |max_size: &str| {
+            format!(
+                "#[assert(codesize = 0x05)]
+                contract C {{
+                    block main {{ push(0x11) push(0x12) }}
+                    #[keep]
+                    #[assert(pc = 0x04)]
+                    #[assert(size <= {})]
+                    block end {{ add }}
+                }}",
+                max_size
+            )
+        };
+        compile_source(&source("0x01"), "C").unwrap();
+
+        let err = compile_source(&source("0x00"), "C").unwrap_err();
+        assert!(err.contains("main.mep:6:21"), "{}", err);
+        assert!(
+            err.contains("Assertion failed: the size of block `end` is 0x1, expected <= 0x0."),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_holes_beyond_64kb() {
+        let source = format!(
+            "contract C {{
+                block main {{ push(pad.size) pop push(end.pc) jump }}
+                #[keep]
+                block pad {{ 0x{} }}
+                block end {{ jumpdest stop }}
+            }}",
+            "00".repeat(0x10000)
+        );
+        let artifacts = compile_source(&source, "C").unwrap();
+        let contract = artifacts.main_artifacts();
+        assert_eq!(contract.blocks["end"].pc, 0x1000a);
+        assert_eq!(
+            hex::encode(&contract.bytecode[..10]),
+            "62010000506201000a56"
+        );
+        assert_eq!(hex::encode(&contract.bytecode[0x1000a..]), "5b00");
+    }
+
+    #[test]
+    fn test_pc_and_codesize_substitutions() {
+        let source = |attribute: &str| {
+            format!(
+                "{}
+                contract C {{
+                    block main {{ push(0x20) push(0x02) push(0x05) stop }}
+                }}",
+                attribute
+            )
+        };
+        let artifacts = compile_source(&source(""), "C").unwrap();
+        assert_eq!(hex::encode(artifacts.main_bytecode()), "6020583800");
+
+        // the constructor arguments are appended to the code, changing its size
+        let artifacts = compile_source(&source("#[constructor_args]"), "C").unwrap();
+        assert_eq!(hex::encode(artifacts.main_bytecode()), "602058600500");
+    }
+
+    #[test]
+    fn test_immutables() {
+        let artifacts = compile_source(
+            "contract Deployed {
+                immutable OWNER: 20;
+                block main { push(OWNER) caller eq push(OWNER) pop pop }
+            }
+            contract Constructor {
+                block main { push(Deployed.immutable.OWNER.offsets) pop }
+            }",
+            "Constructor",
+        )
+        .unwrap();
+        assert_eq!(hex::encode(artifacts.main_bytecode()), "6201001850");
+
+        let deployed = &artifacts.contracts["Deployed"];
+        let placeholder = format!("73{}", "00".repeat(20));
+        assert_eq!(
+            hex::encode(&deployed.bytecode),
+            format!("{placeholder}3314{placeholder}5050")
+        );
+        assert_eq!(deployed.immutable_references["OWNER"], vec![1, 24]);
+    }
+
+    #[test]
+    fn test_explicit_widths() {
+        let artifacts = compile_source(
+            "contract C {
+                block main { push2(end.pc) jump }
+                block end {
+                    jumpdest
+                    #[width(4)]
+                    push(0x01)
+                    push1(0x00)
+                    stop
+                }
+            }",
+            "C",
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(artifacts.main_bytecode()),
+            "610004565b6300000001600000"
+        );
+
+        let err = compile_source(
+            "contract C {
+                block main { push1(0x0100) }
+            }",
+            "C",
+        )
+        .unwrap_err();
+        assert!(err.contains("main.mep:2:30"), "{}", err);
+        assert!(
+            err.contains("The value 0x0100 does not fit in 1 byte(s)."),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_table_entry_read_as_push() {
+        // the low byte of the entry of `t`, the pc of `target`, is read as a push opcode: each
+        // padding moves `target` to a longer push, until its pc is past `push32`
+        let source = format!(
+            "contract C {{
+                block main {{ push(t.pc) pop push(target.pc) jump {} }}
+                table t(width = 2) {{ target }}
+                block target {{ jumpdest stop }}
+            }}",
+            "0x00 ".repeat(88)
+        );
+        let artifacts = compile_source(&source, "C").unwrap();
+        let contract = artifacts.main_artifacts();
+        let table = &contract.blocks["t"];
+        let target = contract.blocks["target"].pc;
+        assert_eq!(table.size, 2 + 32);
+        assert_eq!(
+            &contract.bytecode[table.pc..table.pc + 2],
+            &(target as u16).to_be_bytes()
+        );
+        assert_eq!(&contract.bytecode[target..], &[0x5b, 0x00]);
+    }
+
+    #[test]
+    fn test_table_padding() {
+        // the last entry of `t` is a `push1` opcode, completed by a single byte
+        let artifacts = compile_source(
+            "contract C {
+                block main { push(t.pc) pop push(a.pc) jump }
+                table t(width = 1) { a, 0x60 }
+                block a { jumpdest stop }
+            }",
+            "C",
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(artifacts.main_bytecode()),
+            "6006506009560960005b00"
+        );
+    }
+
+    #[test]
+    fn test_jump_target_not_jumpdest() {
+        let err = compile_source(
+            "contract C {
+                block main { push(0x01) push(t.pc) jumpi push(u.pc) pop stop }
+                block t { stop }
+                #[keep]
+                block u { stop }
+            }",
+            "C",
+        )
+        .unwrap_err();
+        assert!(err.contains("main.mep:2:41"), "{}", err);
+        assert!(
+            err.contains("The jump at pc 0x4 goes to `t` at pc 0xa, which is not a `jumpdest`."),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_raw_jump_in_block_data() {
+        // the raw bytes of `d` are copied as data, never executed
+        let artifacts = compile_source(
+            "contract C {
+                block main { push(d.size) push(d.pc) push(0x) codecopy stop }
+                block d { 0x600556 }
+            }",
+            "C",
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(artifacts.main_bytecode()),
+            "600360075f3900600556"
+        );
+    }
 }
@@ -1,7 +1,7 @@
 use super::artifacts::Artifacts;
 use super::compile::compile_contracts;
+use super::imports::load_file;
 use super::settings::CompilerSettings;
-use crate::ast::RFile;
 use crate::pre_processing::pre_processing::pre_process;
 
 pub fn compile_file(
@@ -9,26 +9,64 @@ pub fn compile_file(
     contract_name: &str,
     settings: CompilerSettings,
 ) -> Result<Artifacts, String> {
-    let input = match std::fs::read_to_string(path) {
-        Ok(input) => input,
-        Err(err) => return Err(format!("Could not open file `{}`: {}", path, err)),
-    };
-
-    let r_file = match RFile::new(input.clone()) {
-        Ok(r_file) => r_file,
-        Err(err) => {
-            return Err(format!("Parsing failed:\n{}", err));
-        }
-    };
+    let (input, r_file) = load_file(path, &settings.remappings)?;
 
-    let pre_processed = match pre_process(&input, r_file, contract_name, &settings.variables) {
+    let pre_processed = match pre_process(
+        &input,
+        r_file,
+        contract_name,
+        settings.evm_version,
+        &settings.opcodes,
+        &settings.variables,
+        settings.stack_analysis,
+    ) {
         Ok(pre_processed) => pre_processed,
         Err(err) => {
             return Err(format!("Pre-processing failed:\n{}", err));
         }
     };
 
-    let artifacts = compile_contracts(pre_processed, settings);
+    match compile_contracts(&input, pre_processed, settings) {
+        Ok(artifacts) => Ok(artifacts),
+        Err(err) => Err(format!("Compilation failed:\n{}", err)),
+    }
+}
+
+/// Writes the files in a new temporary directory and compiles the first one, for the tests.
+#[cfg(test)]
+pub fn compile_sources(
+    files: &[(&str, &str)],
+    contract_name: &str,
+    settings: CompilerSettings,
+) -> Result<Artifacts, String> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!(
+        "meplang-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    for (name, content) in files {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+    let result = compile_file(
+        dir.join(files[0].0).to_str().unwrap(),
+        contract_name,
+        settings,
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+    result
+}
 
-    Ok(artifacts)
+/// Compiles a single source with the default settings, for the tests.
+#[cfg(test)]
+pub fn compile_source(source: &str, contract_name: &str) -> Result<Artifacts, String> {
+    compile_sources(
+        &[("main.mep", source)],
+        contract_name,
+        CompilerSettings::default(),
+    )
 }
@@ -0,0 +1,221 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::ast::{RFile, RImport};
+use crate::parser::error::{PestError, new_error_from_located};
+use crate::parser::parser::Located;
+use crate::parser::source::Sources;
+
+/// Loads the file at `path` and, recursively, every file it imports. The contracts of all the
+/// loaded files are merged into a single [`RFile`].
+pub fn load_file(path: &str, remappings: &[String]) -> Result<(Sources, RFile), String> {
+    let remappings = remappings
+        .iter()
+        .map(|remapping| match remapping.split_once('=') {
+            Some((prefix, target)) if !prefix.is_empty() => Ok((prefix, target)),
+            _ => Err(format!(
+                "Invalid remapping `{}` - ex: `lib/=node_modules/lib/`",
+                remapping
+            )),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut loader = Loader {
+        remappings,
+        sources: Sources::new(),
+        loaded: HashSet::new(),
+        stack: Vec::new(),
+        r_file: RFile::default(),
+    };
+
+    loader.load(PathBuf::from(path), None)?;
+
+    Ok((loader.sources, loader.r_file))
+}
+
+struct Loader<'a> {
+    remappings: Vec<(&'a str, &'a str)>,
+    sources: Sources,
+    loaded: HashSet<PathBuf>,
+    stack: Vec<PathBuf>,
+    r_file: RFile,
+}
+
+impl Loader<'_> {
+    fn load(&mut self, path: PathBuf, import: Option<&Located<RImport>>) -> Result<(), String> {
+        let canonical = match std::fs::canonicalize(&path) {
+            Ok(canonical) => canonical,
+            Err(err) => {
+                return Err(self.import_error(
+                    import,
+                    format!("Could not open file `{}`: {}", path.display(), err),
+                ));
+            }
+        };
+
+        if let Some(position) = self.stack.iter().position(|p| p == &canonical) {
+            let cycle = self.stack[position..]
+                .iter()
+                .chain([&canonical])
+                .map(|p| format!("`{}`", p.display()))
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            return Err(self.import_error(import, format!("Import cycle detected: {}", cycle)));
+        }
+
+        if !self.loaded.insert(canonical.clone()) {
+            return Ok(());
+        }
+
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) => {
+                return Err(self.import_error(
+                    import,
+                    format!("Could not open file `{}`: {}", path.display(), err),
+                ));
+            }
+        };
+
+        let path_str = path.display().to_string();
+        let file = self.sources.add(path_str.clone(), content);
+        let r_file = match RFile::new(&self.sources.get(file).content, file) {
+            Ok(r_file) => r_file,
+            Err(err) => {
+                return Err(format!("Parsing failed:\n{}", err.with_path(&path_str)));
+            }
+        };
+
+        self.stack.push(canonical);
+        self.r_file.attributes.extend(r_file.attributes);
+        self.r_file.contracts.extend(r_file.contracts);
+        for r_import in &r_file.imports {
+            self.load(self.resolve(&path, r_import.path_str()), Some(r_import))?;
+        }
+        self.stack.pop();
+
+        Ok(())
+    }
+
+    /// Remapped imports are resolved from the current directory, the others relatively to the
+    /// importing file.
+    fn resolve(&self, importer: &Path, import: &str) -> PathBuf {
+        let remapping = self
+            .remappings
+            .iter()
+            .filter(|(prefix, _)| import.starts_with(prefix))
+            .max_by_key(|(prefix, _)| prefix.len());
+
+        if let Some((prefix, target)) = remapping {
+            PathBuf::from(format!("{}{}", target, &import[prefix.len()..]))
+        } else {
+            importer.parent().unwrap_or(Path::new("")).join(import)
+        }
+    }
+
+    fn import_error(&self, import: Option<&Located<RImport>>, message: String) -> String {
+        match import {
+            Some(import) => {
+                let err: PestError = new_error_from_located(&self.sources, import, &message);
+                format!("Import failed:\n{}", err)
+            }
+            None => message,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile::file::compile_sources;
+    use crate::compile::settings::CompilerSettings;
+
+    #[test]
+    fn test_transitive_imports() {
+        let artifacts = compile_sources(
+            &[
+                (
+                    "main.mep",
+                    "import \"lib/lib.mep\";\ncontract C { block main { origin &Lib.code } }",
+                ),
+                (
+                    "lib/lib.mep",
+                    "import \"base.mep\";\ncontract Lib { block main { address &Base.code } }",
+                ),
+                ("lib/base.mep", "contract Base { block main { caller } }"),
+            ],
+            "C",
+            CompilerSettings::default(),
+        )
+        .unwrap();
+        assert_eq!(hex::encode(artifacts.main_bytecode()), "323033");
+    }
+
+    #[test]
+    fn test_error_in_imported_file() {
+        let err = compile_sources(
+            &[
+                (
+                    "main.mep",
+                    "import \"lib/lib.mep\";\ncontract C { block main { &Lib.code } }",
+                ),
+                (
+                    "lib/lib.mep",
+                    "contract Lib {\n    block main { push(0x01) foo }\n}",
+                ),
+            ],
+            "C",
+            CompilerSettings::default(),
+        )
+        .unwrap_err();
+        assert!(err.contains("lib/lib.mep:2:29"), "{}", err);
+    }
+
+    #[test]
+    fn test_import_cycle() {
+        let err = compile_sources(
+            &[
+                (
+                    "main.mep",
+                    "import \"a.mep\";\ncontract C { block main { stop } }",
+                ),
+                (
+                    "a.mep",
+                    "import \"main.mep\";\ncontract A { block main { stop } }",
+                ),
+            ],
+            "C",
+            CompilerSettings::default(),
+        )
+        .unwrap_err();
+        assert!(err.contains("a.mep:1:1"), "{}", err);
+        assert!(err.contains("Import cycle detected"), "{}", err);
+    }
+
+    #[test]
+    fn test_remappings() {
+        let loader = Loader {
+            remappings: vec![("@helpers/", "lib/helpers/src/"), ("@helpers/x/", "x/")],
+            sources: Sources::new(),
+            loaded: HashSet::new(),
+            stack: Vec::new(),
+            r_file: RFile::default(),
+        };
+        let importer = Path::new("src/main.mep");
+        assert_eq!(
+            loader.resolve(importer, "@helpers/revert.mep"),
+            PathBuf::from("lib/helpers/src/revert.mep")
+        );
+        assert_eq!(
+            loader.resolve(importer, "@helpers/x/math.mep"),
+            PathBuf::from("x/math.mep")
+        );
+        assert_eq!(
+            loader.resolve(importer, "lib/math.mep"),
+            PathBuf::from("src/lib/math.mep")
+        );
+
+        let err = load_file("main.mep", &["@helpers/".to_owned()]).unwrap_err();
+        assert!(err.contains("Invalid remapping `@helpers/`"), "{}", err);
+    }
+}
@@ -0,0 +1,94 @@
+use bytes::{Bytes, BytesMut};
+
+use super::optimizer::push_constant;
+use crate::pre_processing::opcode::{CODECOPY, CODESIZE, DUP1, RETURN, SUB};
+use crate::types::bytes32::Bytes32;
+
+/// Deployment code of a contract, made of a minimal constructor followed by the code:
+/// `size dup1 offset push0 codecopy push0 return`. With `constructor_args`, the arguments appended
+/// to the deployment code are returned after the code, where the deployed contract can read them:
+/// `offset codesize sub dup1 offset push0 codecopy push0 return`.
+pub fn initcode(bytecode: &Bytes, push0: bool, constructor_args: bool) -> Bytes {
+    let zero = push_constant(&Bytes32::default(), push0);
+    // the offset of the code is the size of the constructor, which always fits in a byte
+    let size = if constructor_args {
+        None
+    } else {
+        Some(push_constant(&Bytes32::from_usize(bytecode.len()), push0))
+    };
+    // `offset codesize sub` is 4 bytes long, `dup1 offset codecopy return` 5 bytes
+    let size_len = size.as_ref().map_or(4, |size| size.len());
+    let offset = push_constant(&Bytes32::from_usize(size_len + 5 + 2 * zero.len()), push0);
+
+    let mut res = BytesMut::new();
+    match size {
+        Some(size) => res.extend_from_slice(&size),
+        None => {
+            res.extend_from_slice(&offset);
+            res.extend_from_slice(&[CODESIZE, SUB]);
+        }
+    }
+    res.extend_from_slice(&[DUP1]);
+    res.extend_from_slice(&offset);
+    res.extend_from_slice(&zero);
+    res.extend_from_slice(&[CODECOPY]);
+    res.extend_from_slice(&zero);
+    res.extend_from_slice(&[RETURN]);
+    res.extend_from_slice(bytecode);
+    res.freeze()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compile::file::{compile_source, compile_sources};
+    use crate::compile::settings::CompilerSettings;
+    use crate::pre_processing::evm_version::EvmVersion;
+
+    #[test]
+    fn test_initcode() {
+        let source = "contract D { block main { caller } }";
+        let artifacts = compile_source(source, "D").unwrap();
+        assert_eq!(
+            hex::encode(&artifacts.main_artifacts().initcode),
+            "60018060095f395ff333"
+        );
+
+        let settings = CompilerSettings {
+            evm_version: EvmVersion::London,
+            ..Default::default()
+        };
+        let artifacts = compile_sources(&[("main.mep", source)], "D", settings).unwrap();
+        assert_eq!(
+            hex::encode(&artifacts.main_artifacts().initcode),
+            "600180600b6000396000f333"
+        );
+
+        let artifacts = compile_source(
+            "#[constructor_args]\ncontract D { block main { caller } }",
+            "D",
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(&artifacts.main_artifacts().initcode),
+            "600b380380600b5f395ff333"
+        );
+    }
+
+    #[test]
+    fn test_copied_initcode() {
+        let artifacts = compile_source(
+            "contract D { block main { caller } }
+            contract F {
+                block main { push(D.initcode.size) pop stop }
+                #[keep]
+                block d { &D.initcode }
+            }",
+            "F",
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(artifacts.main_bytecode()),
+            "600a500060018060095f395ff333"
+        );
+    }
+}
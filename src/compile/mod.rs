@@ -2,4 +2,7 @@ pub mod artifacts;
 pub mod compile;
 pub mod file;
 mod fillers;
+mod imports;
+mod initcode;
+mod optimizer;
 pub mod settings;
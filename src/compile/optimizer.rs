@@ -0,0 +1,301 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use super::settings::{CompilerSettings, CostModel};
+use crate::pre_processing::opcode::{
+    ADD, BLOCKHASH, NOT, OpCode, PUSH0, PUSH1, PUSH32, SHL, SHR, SUB,
+};
+use crate::types::bytes32::Bytes32;
+
+/// Number of nested operations allowed to synthesize a constant.
+const MAX_DEPTH: usize = 2;
+
+/// Largest size in bytes of the difference between a constant and an assumed value for it to be
+/// synthesized with `add` or `sub`.
+const MAX_DELTA_LEN: usize = 2;
+
+const PUSH_GAS: usize = 3;
+const OPERATION_GAS: usize = 3;
+
+/// Sequence of opcodes pushing a single value on the stack.
+#[derive(Clone, Debug)]
+struct Synthesis {
+    code: Vec<u8>,
+    gas: usize,
+}
+
+impl Synthesis {
+    fn then(mut self, other: Synthesis) -> Self {
+        self.code.extend(other.code);
+        self.gas += other.gas;
+        self
+    }
+
+    fn then_op(mut self, op: OpCode) -> Self {
+        self.code.push(op);
+        self.gas += OPERATION_GAS;
+        self
+    }
+}
+
+/// The minimal `PUSHn` of a value, or `PUSH0` for zero if allowed.
+pub fn push_constant(value: &Bytes32, push0: bool) -> Vec<u8> {
+    if value.is_zero() {
+        return if push0 {
+            vec![PUSH0]
+        } else {
+            vec![PUSH1, 0x00]
+        };
+    }
+    let content = value.right_content();
+    let push = PUSH0 + (content.len() as u8);
+    assert!(PUSH0 < push && push <= PUSH32);
+    let mut code = vec![push];
+    code.extend_from_slice(content);
+    code
+}
+
+/// The cheapest sequence of opcodes found to push a value, according to the cost model of the
+/// settings. Besides `PUSHn`, the value can be pushed by an assumed opcode, or computed with `not`,
+/// `shl`, `shr`, `add` and `sub` from smaller values - ex: `push0 not` for `0xff...ff`.
+pub fn synthesize(
+    value: &Bytes32,
+    assumes: &HashMap<Bytes32, OpCode>,
+    settings: &CompilerSettings,
+) -> Vec<u8> {
+    // sorted so that the result does not depend on the order of the map
+    let mut assumes = assumes
+        .iter()
+        .map(|(value, op)| (value.clone(), *op))
+        .collect::<Vec<_>>();
+    assumes.sort_by_key(|(_, op)| *op);
+
+    let synthesizer = Synthesizer {
+        assumes: &assumes,
+        push0: settings.use_push0(),
+        shifts: settings.evm_version.has_shifts(),
+        cost_model: settings.cost_model,
+    };
+    synthesizer.best(value, MAX_DEPTH).code
+}
+
+struct Synthesizer<'a> {
+    assumes: &'a [(Bytes32, OpCode)],
+    push0: bool,
+    shifts: bool,
+    cost_model: CostModel,
+}
+
+impl Synthesizer<'_> {
+    fn compare(&self, a: &Synthesis, b: &Synthesis) -> Ordering {
+        match self.cost_model {
+            CostModel::Size => (a.code.len(), a.gas).cmp(&(b.code.len(), b.gas)),
+            CostModel::Gas => (a.gas, a.code.len()).cmp(&(b.gas, b.code.len())),
+        }
+    }
+
+    /// A single opcode pushing the value.
+    fn atom(&self, value: &Bytes32) -> Synthesis {
+        let mut best = Synthesis {
+            code: push_constant(value, self.push0),
+            gas: if value.is_zero() && self.push0 {
+                2
+            } else {
+                PUSH_GAS
+            },
+        };
+        for (assumed, op) in self.assumes {
+            let candidate = Synthesis {
+                code: vec![*op],
+                gas: if *op == BLOCKHASH { 20 } else { 2 },
+            };
+            if assumed == value && self.compare(&candidate, &best).is_lt() {
+                best = candidate;
+            }
+        }
+        best
+    }
+
+    fn best(&self, value: &Bytes32, depth: usize) -> Synthesis {
+        let mut best = self.atom(value);
+        if depth == 0 || best.code.len() == 1 {
+            return best;
+        }
+
+        let mut candidates = Vec::<Synthesis>::new();
+
+        candidates.push(self.best(&!value, depth - 1).then_op(NOT));
+
+        // `value = a << shift`, the bits of `a` shifted out can be anything
+        let shift = value.trailing_zero_bits();
+        if self.shifts && 0 < shift && shift < 256 {
+            let low = value.shr(shift);
+            let high = &low | &(!&Bytes32::default()).shl(256 - shift);
+            for a in [low, high] {
+                candidates.push(
+                    self.best(&a, depth - 1)
+                        .then(self.atom(&Bytes32::from_usize(shift)))
+                        .then_op(SHL),
+                );
+            }
+        }
+
+        // `value = a >> shift`, the bits of `a` shifted out can be anything
+        let shift = value.leading_zero_bits();
+        if self.shifts && 0 < shift && shift < 256 {
+            let high = value.shl(shift);
+            let low = &high | &(!&Bytes32::default()).shr(256 - shift);
+            for a in [high, low] {
+                candidates.push(
+                    self.best(&a, depth - 1)
+                        .then(self.atom(&Bytes32::from_usize(shift)))
+                        .then_op(SHR),
+                );
+            }
+        }
+
+        // `value = assumed + delta` or `value = assumed - delta`
+        for (assumed, _) in self.assumes {
+            let assumed_atom = self.atom(assumed);
+            let (delta, _) = value.overflowing_sub(assumed);
+            if delta.right_content().len() <= MAX_DELTA_LEN {
+                candidates.push(self.atom(&delta).then(assumed_atom.clone()).then_op(ADD));
+            }
+            let (delta, _) = assumed.overflowing_sub(value);
+            if delta.right_content().len() <= MAX_DELTA_LEN {
+                candidates.push(self.atom(&delta).then(assumed_atom).then_op(SUB));
+            }
+        }
+
+        for candidate in candidates {
+            if self.compare(&candidate, &best).is_lt() {
+                best = candidate;
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile::file::compile_source;
+    use crate::pre_processing::opcode::ADDRESS;
+
+    fn word(hex: &str) -> Bytes32 {
+        Bytes32::from_bytes(&hex::decode(hex).unwrap().into(), true).unwrap()
+    }
+
+    fn synthesize_hex(
+        value: &Bytes32,
+        assumes: &[(Bytes32, OpCode)],
+        cost_model: CostModel,
+    ) -> String {
+        let settings = CompilerSettings {
+            cost_model,
+            ..Default::default()
+        };
+        hex::encode(synthesize(
+            value,
+            &assumes.iter().cloned().collect(),
+            &settings,
+        ))
+    }
+
+    #[test]
+    fn test_synthesize_not() {
+        let value = !&Bytes32::default();
+        assert_eq!(synthesize_hex(&value, &[], CostModel::Size), "5f19");
+        assert_eq!(
+            synthesize_hex(&value, &[], CostModel::Gas),
+            format!("7f{}", "ff".repeat(32))
+        );
+    }
+
+    #[test]
+    fn test_synthesize_shifts() {
+        // `1 << 255`: the value is pushed before the shift
+        let value = Bytes32::from_usize(1).shl(255);
+        assert_eq!(synthesize_hex(&value, &[], CostModel::Size), "600160ff1b");
+        assert_eq!(
+            synthesize_hex(&value, &[], CostModel::Gas),
+            format!("7f80{}", "00".repeat(31))
+        );
+
+        // `0xffffffff << 224`, computed from `0xff...ff << 224`
+        let value = word(&format!("ffffffff{}", "00".repeat(28)));
+        assert_eq!(synthesize_hex(&value, &[], CostModel::Size), "5f1960e01b");
+        assert_eq!(
+            synthesize_hex(&value, &[], CostModel::Gas),
+            format!("7fffffffff{}", "00".repeat(28))
+        );
+
+        // `0x7fff...ff`, computed from `0xff...ff >> 1`
+        let value = (!&Bytes32::default()).shr(1);
+        assert_eq!(synthesize_hex(&value, &[], CostModel::Size), "5f1960011c");
+        assert_eq!(
+            synthesize_hex(&value, &[], CostModel::Gas),
+            format!("7f7f{}", "ff".repeat(31))
+        );
+    }
+
+    #[test]
+    fn test_synthesize_assumed() {
+        let address = word("c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2");
+        let assumes = [(address.clone(), ADDRESS)];
+        let delta = Bytes32::from_usize(0x10);
+
+        for cost_model in [CostModel::Size, CostModel::Gas] {
+            assert_eq!(synthesize_hex(&address, &assumes, cost_model), "30");
+        }
+
+        // `address + 0x10`
+        let value = address.overflowing_add(&delta).0;
+        assert_eq!(
+            synthesize_hex(&value, &assumes, CostModel::Size),
+            "60103001"
+        );
+        assert_eq!(
+            synthesize_hex(&value, &assumes, CostModel::Gas),
+            "73c02aaa39b223fe8d0a0e5c4f27ead9083c756cd2"
+        );
+
+        // `address - 0x10`: the assumed value is on top of the stack for `sub`
+        let value = address.overflowing_sub(&delta).0;
+        assert_eq!(
+            synthesize_hex(&value, &assumes, CostModel::Size),
+            "60103003"
+        );
+        assert_eq!(
+            synthesize_hex(&value, &assumes, CostModel::Gas),
+            "73c02aaa39b223fe8d0a0e5c4f27ead9083c756cb2"
+        );
+    }
+
+    #[test]
+    fn test_optimization_disabled() {
+        let max = format!("0x{}", "ff".repeat(32));
+        let artifacts = compile_source(
+            &format!(
+                "contract C {{
+                    #[assume(msize = 0x20)]
+                    block main {{
+                        push({max}) push(0x20)
+                        *raw
+                    }}
+
+                    #[disable_optimization]
+                    block raw {{
+                        push({max}) push(0x20)
+                    }}
+                }}"
+            ),
+            "C",
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(artifacts.main_bytecode()),
+            format!("5f19597f{}6020", "ff".repeat(32))
+        );
+    }
+}
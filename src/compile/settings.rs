@@ -4,6 +4,9 @@ use bytes::Bytes;
 use serde::ser::SerializeMap;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+use crate::pre_processing::evm_version::EvmVersion;
+use crate::pre_processing::opcode::CustomOpcode;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(deny_unknown_fields, rename_all = "camelCase")]
 pub struct CompilerSettings {
@@ -17,6 +20,17 @@ pub struct CompilerSettings {
         deserialize_with = "deserialize_variables"
     )]
     pub variables: HashMap<String, Bytes>,
+    #[serde(default)]
+    pub remappings: Vec<String>,
+    #[serde(default)]
+    pub cost_model: CostModel,
+    #[serde(default)]
+    pub evm_version: EvmVersion,
+    #[serde(default)]
+    pub opcodes: Vec<CustomOpcode>,
+    /// Whether the stack height is checked along the paths followed from `main`.
+    #[serde(default = "bool_true")]
+    pub stack_analysis: bool,
 }
 
 impl Default for CompilerSettings {
@@ -25,6 +39,11 @@ impl Default for CompilerSettings {
             push0: true,
             filling_pattern: FillingPatern::default(),
             variables: HashMap::default(),
+            remappings: Vec::default(),
+            cost_model: CostModel::default(),
+            evm_version: EvmVersion::default(),
+            opcodes: Vec::default(),
+            stack_analysis: true,
         }
     }
 }
@@ -33,6 +52,26 @@ impl CompilerSettings {
     pub fn add_variable(&mut self, name: &str, value: Bytes) {
         self.variables.insert(name.to_owned(), value);
     }
+
+    /// Whether `PUSH0` can be emitted, which requires shanghai.
+    pub fn use_push0(&self) -> bool {
+        self.push0 && self.evm_version.has_push0()
+    }
+
+    pub fn add_remapping(&mut self, prefix: &str, target: &str) {
+        self.remappings.push(format!("{}={}", prefix, target));
+    }
+}
+
+/// What the synthesis of the pushed constants minimizes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CostModel {
+    /// Number of bytes of the code, then gas.
+    #[default]
+    Size,
+    /// Gas spent at execution, then number of bytes of the code.
+    Gas,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -7,6 +7,8 @@ mod parser;
 mod pre_processing;
 mod types;
 
-pub use compile::artifacts::{Artifacts, BlockArtifacts, ContractArtifacts};
+pub use compile::artifacts::{Artifacts, BlockArtifacts, ContractArtifacts, LinkReference};
 pub use compile::file::compile_file;
-pub use compile::settings::{CompilerSettings, FillingPatern};
+pub use compile::settings::{CompilerSettings, CostModel, FillingPatern};
+pub use pre_processing::evm_version::EvmVersion;
+pub use pre_processing::opcode::CustomOpcode;
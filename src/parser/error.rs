@@ -3,6 +3,7 @@ use pest::iterators::Pair;
 use pest::{Position, Span};
 
 use super::parser::{Located, Location};
+use super::source::Sources;
 use crate::parser::parser::Rule;
 
 pub type PestError = Box<pest::error::Error<Rule>>;
@@ -21,15 +22,23 @@ pub fn new_generic_error(message: String) -> PestError {
     ))
 }
 
-pub fn new_error_from_located<T>(input: &str, located: &Located<T>, message: &str) -> PestError {
+pub fn new_error_from_located<T>(
+    input: &Sources,
+    located: &Located<T>,
+    message: &str,
+) -> PestError {
     new_error_from_location(input, &located.location, message)
 }
 
-pub fn new_error_from_location(input: &str, location: &Location, message: &str) -> PestError {
-    Box::new(pest::error::Error::<Rule>::new_from_span(
-        ErrorVariant::<Rule>::CustomError {
-            message: message.to_owned(),
-        },
-        Span::new(input, location.start, location.end).unwrap(),
-    ))
+pub fn new_error_from_location(input: &Sources, location: &Location, message: &str) -> PestError {
+    let file = input.get(location.file);
+    Box::new(
+        pest::error::Error::<Rule>::new_from_span(
+            ErrorVariant::<Rule>::CustomError {
+                message: message.to_owned(),
+            },
+            Span::new(&file.content, location.start, location.end).unwrap(),
+        )
+        .with_path(&file.path),
+    )
 }
@@ -5,6 +5,7 @@ use pest::iterators::{Pair, Pairs};
 use pest_derive::Parser;
 
 use crate::parser::error::PestError;
+use crate::parser::source::FileId;
 
 #[derive(Parser)]
 #[grammar = "./src/parser/meplang.pest"]
@@ -14,10 +15,10 @@ pub trait FromPair
 where
     Self: Sized + Debug + Clone,
 {
-    fn from_pair(pair: Pair<Rule>) -> Result<Self, Box<pest::error::Error<Rule>>>;
+    fn from_pair(pair: Pair<Rule>, file: FileId) -> Result<Self, Box<pest::error::Error<Rule>>>;
 }
 
-pub fn map_unique_child<T>(pair: Pair<Rule>, f: fn(Pair<Rule>) -> T) -> T {
+pub fn map_unique_child<T>(pair: Pair<Rule>, f: impl FnOnce(Pair<Rule>) -> T) -> T {
     let mut inner = pair.into_inner();
     let child = inner.next().unwrap();
     let res = f(child);
@@ -37,20 +38,22 @@ pub struct Located<T> {
     pub inner: T,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Location {
+    pub file: FileId,
     pub start: usize,
     pub end: usize,
 }
 
 impl<T: FromPair> FromPair for Located<T> {
-    fn from_pair(pair: Pair<'_, Rule>) -> Result<Located<T>, PestError> {
+    fn from_pair(pair: Pair<'_, Rule>, file: FileId) -> Result<Located<T>, PestError> {
         Ok(Self {
             location: Location {
+                file,
                 start: pair.as_span().start(),
                 end: pair.as_span().end(),
             },
-            inner: T::from_pair(pair)?,
+            inner: T::from_pair(pair, file)?,
         })
     }
 }
@@ -0,0 +1,27 @@
+pub type FileId = usize;
+
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub path: String,
+    pub content: String,
+}
+
+/// All the files loaded during a compilation, indexed by [`FileId`]. The file `0` is the one given
+/// to the compiler, the others are loaded through `import` directives.
+#[derive(Debug, Clone, Default)]
+pub struct Sources(Vec<SourceFile>);
+
+impl Sources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, path: String, content: String) -> FileId {
+        self.0.push(SourceFile { path, content });
+        self.0.len() - 1
+    }
+
+    pub fn get(&self, file: FileId) -> &SourceFile {
+        &self.0[file]
+    }
+}
@@ -3,19 +3,25 @@ use std::collections::HashMap;
 use bytes::Bytes;
 use quick_impl::quick_impl_all;
 
+use super::evm_version::{EvmVersion, check_evm_version};
 use super::opcode::*;
 use super::pre_processing::get_compile_variable_value;
-use crate::ast::{RAttribute, RAttributeArg, RAttributeEqualityRight};
+use crate::ast::{RAttribute, RAttributeArg, RAttributeEqualityRight, RComparisonOperator};
 use crate::parser::error::{PestError, new_error_from_located};
-use crate::parser::parser::Located;
+use crate::parser::parser::{Located, Location};
+use crate::parser::source::Sources;
 use crate::types::bytes32::Bytes32;
 
+const STACK_EXAMPLE: &str = "Expected the stack inputs and outputs of the block, or the names of the items on the stack - ex: #[stack(in = 2, out = 1)] or #[stack(a, b)]";
+
 #[rustfmt::skip]
-const fn is_assumable_opcode(op: OpCode) -> bool {
+fn is_assumable_opcode(op: OpCode, evm_version: EvmVersion) -> bool {
     match op {
         ADDRESS | ORIGIN | CALLER | CALLVALUE | CALLDATASIZE | GASPRICE | RETURNDATASIZE |
-        BLOCKHASH | COINBASE | TIMESTAMP | NUMBER | DIFFICULTY /* | RANDOM | PREVRANDAO */ | GASLIMIT | CHAINID | 
-        BASEFEE | MSIZE => true,
+        BLOCKHASH | COINBASE | TIMESTAMP | NUMBER | GASLIMIT | CHAINID | 
+        BASEFEE | BLOBBASEFEE | MSIZE => true,
+        // replaced by `prevrandao` since the merge
+        DIFFICULTY => evm_version < EvmVersion::Paris,
         _ => false,
     }
 }
@@ -23,25 +29,125 @@ const fn is_assumable_opcode(op: OpCode) -> bool {
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[quick_impl_all(pub const is)]
 pub enum Attribute {
-    Assume { op: u8, v: Bytes32 },
-    ClearAssume { op: u8 },
+    Assume {
+        op: u8,
+        v: Bytes32,
+    },
+    ClearAssume {
+        op: u8,
+    },
     Keep,
     Main,
     Last,
     Optimization(bool),
+    Assert(Assertion),
+    EvmVersion(EvmVersion),
+    ConstructorArgs,
+    /// Exact number of bytes pushed by the next push.
+    Width(usize),
+    Stack(StackAnnotation),
+}
+
+/// Stack effect declared on a block, checked against the stack height analysis, and names of the
+/// items on the stack.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StackAnnotation {
+    pub inputs: Option<usize>,
+    pub outputs: Option<usize>,
+    /// Names of the items on top of the stack, the first being the top.
+    pub layout: Vec<String>,
+    pub location: Location,
+}
+
+/// Condition on the layout of the compiled contract, checked once the contract is compiled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Assertion {
+    pub subject: AssertionSubject,
+    pub comparison: Comparison,
+    pub value: usize,
+    pub location: Location,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[quick_impl_all(pub const is)]
+pub enum AssertionSubject {
+    Pc,
+    Size,
+    Codesize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Le,
+    Ge,
+    Lt,
+    Gt,
+}
+
+impl Comparison {
+    pub fn holds(self, actual: usize, expected: usize) -> bool {
+        match self {
+            Self::Eq => actual == expected,
+            Self::Le => actual <= expected,
+            Self::Ge => actual >= expected,
+            Self::Lt => actual < expected,
+            Self::Gt => actual > expected,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Eq => "=",
+            Self::Le => "<=",
+            Self::Ge => ">=",
+            Self::Lt => "<",
+            Self::Gt => ">",
+        }
+    }
+}
+
+impl From<RComparisonOperator> for Comparison {
+    fn from(operator: RComparisonOperator) -> Self {
+        match operator {
+            RComparisonOperator::Le => Self::Le,
+            RComparisonOperator::Ge => Self::Ge,
+            RComparisonOperator::Lt => Self::Lt,
+            RComparisonOperator::Gt => Self::Gt,
+        }
+    }
 }
 
 impl Attribute {
     pub fn is_contract_attribute(&self) -> bool {
-        !self.is_main() && !self.is_last() && !self.is_keep()
+        match self {
+            Self::Assert(assertion) => assertion.subject.is_codesize(),
+            _ => {
+                !self.is_main()
+                    && !self.is_last()
+                    && !self.is_keep()
+                    && !self.is_width()
+                    && !self.is_stack()
+            }
+        }
     }
 
     pub fn is_block_attribute(&self) -> bool {
-        true
+        match self {
+            Self::Assert(assertion) => !assertion.subject.is_codesize(),
+            Self::EvmVersion(_) | Self::ConstructorArgs | Self::Width(_) => false,
+            _ => true,
+        }
     }
 
     pub fn is_abstract_block_attribute(&self) -> bool {
-        !self.is_main() && !self.is_last() && !self.is_keep()
+        !self.is_main()
+            && !self.is_last()
+            && !self.is_keep()
+            && !self.is_assert()
+            && !self.is_evm_version()
+            && !self.is_constructor_args()
+            && !self.is_width()
     }
 
     pub fn is_block_item_attribute(&self) -> bool {
@@ -52,14 +158,22 @@ impl Attribute {
     }
 
     pub fn from_r_attribute(
-        input: &str,
+        input: &Sources,
         r_attribute: &Located<RAttribute>,
+        evm_version: EvmVersion,
         compile_variables: &HashMap<String, Bytes>,
     ) -> Result<Self, PestError> {
         let name = r_attribute.name_str();
+        if r_attribute.args.len() > 1 && name != "stack" {
+            return Err(new_error_from_located(
+                input,
+                &r_attribute.args[1],
+                &format!("The attribute `{}` takes a single argument.", name),
+            ));
+        }
         match name {
             "assume" => {
-                let Some(arg) = &r_attribute.arg else {
+                let Some(arg) = r_attribute.arg() else {
                     return Err(new_error_from_located(
                         input,
                         r_attribute,
@@ -77,6 +191,9 @@ impl Attribute {
 
                 let bytes = match &eq.value.inner {
                     RAttributeEqualityRight::HexLiteral(hex_literal) => hex_literal.0.clone(),
+                    RAttributeEqualityRight::DecimalLiteral(decimal_literal) => {
+                        Bytes::copy_from_slice(decimal_literal.0.right_content())
+                    }
                     RAttributeEqualityRight::CompileVariable(compile_variable) => {
                         get_compile_variable_value(input, compile_variable, compile_variables)?
                             .clone()
@@ -106,7 +223,11 @@ impl Attribute {
                     ));
                 };
 
-                if is_assumable_opcode(op) {
+                if let Err(err) = check_evm_version(op, eq.name_str(), evm_version) {
+                    return Err(new_error_from_located(input, &eq.name, &err));
+                }
+
+                if is_assumable_opcode(op, evm_version) {
                     let Some(formatted) = Bytes32::from_bytes(&bytes, true) else {
                         return Err(new_error_from_located(
                             input,
@@ -125,7 +246,7 @@ impl Attribute {
                 }
             }
             "clear_assume" => {
-                let Some(arg) = &r_attribute.arg else {
+                let Some(arg) = r_attribute.arg() else {
                     return Err(new_error_from_located(
                         input,
                         r_attribute,
@@ -149,7 +270,11 @@ impl Attribute {
                     ));
                 };
 
-                if is_assumable_opcode(op) {
+                if let Err(err) = check_evm_version(op, var.as_str(), evm_version) {
+                    return Err(new_error_from_located(input, arg, &err));
+                }
+
+                if is_assumable_opcode(op, evm_version) {
                     Ok(Self::ClearAssume { op })
                 } else {
                     Err(new_error_from_located(
@@ -159,6 +284,164 @@ impl Attribute {
                     ))
                 }
             }
+            "assert" => {
+                let Some(arg) = r_attribute.arg() else {
+                    return Err(new_error_from_located(
+                        input,
+                        r_attribute,
+                        "Argument required after `assert` attribute - ex: #[assert(size <= 0x40)]",
+                    ));
+                };
+
+                let (name, comparison, value) = match &arg.inner {
+                    RAttributeArg::AttributeEquality(eq) => (&eq.name, Comparison::Eq, &eq.value),
+                    RAttributeArg::AttributeComparison(cmp) => {
+                        (&cmp.name, cmp.operator.inner.into(), &cmp.value)
+                    }
+                    _ => {
+                        return Err(new_error_from_located(
+                            input,
+                            r_attribute,
+                            "Expected comparison - ex: #[assert(size <= 0x40)]",
+                        ));
+                    }
+                };
+
+                let subject = match name.as_str() {
+                    "pc" => AssertionSubject::Pc,
+                    "size" => AssertionSubject::Size,
+                    "codesize" => AssertionSubject::Codesize,
+                    _ => {
+                        return Err(new_error_from_located(
+                            input,
+                            name,
+                            "Expected `pc`, `size` or `codesize`.",
+                        ));
+                    }
+                };
+
+                let bytes = match &value.inner {
+                    RAttributeEqualityRight::HexLiteral(hex_literal) => hex_literal.0.clone(),
+                    RAttributeEqualityRight::DecimalLiteral(decimal_literal) => {
+                        Bytes::copy_from_slice(decimal_literal.0.right_content())
+                    }
+                    RAttributeEqualityRight::CompileVariable(compile_variable) => {
+                        get_compile_variable_value(input, compile_variable, compile_variables)?
+                            .clone()
+                    }
+                    _ => {
+                        return Err(new_error_from_located(
+                            input,
+                            value,
+                            "Expected: \n hex literal - ex: #[assert(pc = 0x100)] \n or compile variable - ex: #[assert(pc = $PC$)]",
+                        ));
+                    }
+                };
+
+                let Some(value) = Bytes32::from_bytes(&bytes, true).and_then(|v| v.to_usize())
+                else {
+                    return Err(new_error_from_located(input, value, "Value too large."));
+                };
+
+                Ok(Self::Assert(Assertion {
+                    subject,
+                    comparison,
+                    value,
+                    location: r_attribute.location.clone(),
+                }))
+            }
+            "evm_version" => {
+                let Some(RAttributeArg::StringLiteral(version)) =
+                    r_attribute.arg().map(|arg| &arg.inner)
+                else {
+                    return Err(new_error_from_located(
+                        input,
+                        r_attribute,
+                        "EVM version required after `evm_version` attribute - ex: #[evm_version(\"cancun\")]",
+                    ));
+                };
+
+                let Some(version) = EvmVersion::from_name(version.as_str()) else {
+                    return Err(new_error_from_located(
+                        input,
+                        r_attribute.arg().unwrap(),
+                        &format!("Unknown EVM version `{}`.", version.as_str()),
+                    ));
+                };
+
+                Ok(Self::EvmVersion(version))
+            }
+            "constructor_args" => Ok(Self::ConstructorArgs),
+            "width" => {
+                let Some(RAttributeArg::DecimalLiteral(width)) =
+                    r_attribute.arg().map(|arg| &arg.inner)
+                else {
+                    return Err(new_error_from_located(
+                        input,
+                        r_attribute,
+                        "Number of bytes required after `width` attribute - ex: #[width(2)]",
+                    ));
+                };
+
+                match width.0.to_usize() {
+                    Some(width) if (1..=32).contains(&width) => Ok(Self::Width(width)),
+                    _ => Err(new_error_from_located(
+                        input,
+                        r_attribute.arg().unwrap(),
+                        "The width of a push must be between 1 and 32 bytes.",
+                    )),
+                }
+            }
+            "stack" => {
+                let mut annotation = StackAnnotation {
+                    inputs: None,
+                    outputs: None,
+                    layout: Vec::new(),
+                    location: r_attribute.location.clone(),
+                };
+                for arg in &r_attribute.args {
+                    let eq = match &arg.inner {
+                        RAttributeArg::Variable(name) => {
+                            annotation.layout.push(name.as_str().to_owned());
+                            continue;
+                        }
+                        RAttributeArg::AttributeEquality(eq) => eq,
+                        _ => return Err(new_error_from_located(input, arg, STACK_EXAMPLE)),
+                    };
+                    let count = match eq.name_str() {
+                        "in" => &mut annotation.inputs,
+                        "out" => &mut annotation.outputs,
+                        _ => {
+                            return Err(new_error_from_located(input, &eq.name, STACK_EXAMPLE));
+                        }
+                    };
+                    if count.is_some() {
+                        return Err(new_error_from_located(
+                            input,
+                            &eq.name,
+                            &format!("`{}` is already declared.", eq.name_str()),
+                        ));
+                    }
+                    let Some(value) = number_value(input, &eq.value, compile_variables)?
+                        .to_usize()
+                        .filter(|value| *value <= 1024)
+                    else {
+                        return Err(new_error_from_located(
+                            input,
+                            &eq.value,
+                            "The stack holds at most 1024 items.",
+                        ));
+                    };
+                    *count = Some(value);
+                }
+                if annotation.inputs.is_none()
+                    && annotation.outputs.is_none()
+                    && annotation.layout.is_empty()
+                {
+                    return Err(new_error_from_located(input, r_attribute, STACK_EXAMPLE));
+                }
+                Ok(Self::Stack(annotation))
+            }
             "enable_optimization" => Ok(Self::Optimization(true)),
             "disable_optimization" => Ok(Self::Optimization(false)),
             "keep" => Ok(Self::Keep),
@@ -208,3 +491,187 @@ impl Attributes {
         }
     }
 }
+
+const OPCODE_EXAMPLE: &str = "ex: #![opcode(l1blocknumber = 0x4b, inputs = 0, outputs = 1)]";
+
+/// Custom opcode declared at the top of a file - ex:
+/// `#![opcode(l1blocknumber = 0x4b, inputs = 0, outputs = 1)]`.
+pub fn custom_opcode_from_r_attribute(
+    input: &Sources,
+    r_attribute: &Located<RAttribute>,
+    compile_variables: &HashMap<String, Bytes>,
+) -> Result<CustomOpcode, PestError> {
+    if r_attribute.name_str() != "opcode" {
+        return Err(new_error_from_located(
+            input,
+            &r_attribute.name,
+            &format!("Unknown file attribute `{}`", r_attribute.name_str()),
+        ));
+    }
+
+    let Some(RAttributeArg::AttributeEquality(declaration)) =
+        r_attribute.arg().map(|arg| &arg.inner)
+    else {
+        return Err(new_error_from_located(
+            input,
+            r_attribute,
+            &format!("Expected opcode declaration - {}", OPCODE_EXAMPLE),
+        ));
+    };
+    let Some(opcode) = number_value(input, &declaration.value, compile_variables)?
+        .to_usize()
+        .and_then(|opcode| OpCode::try_from(opcode).ok())
+    else {
+        return Err(new_error_from_located(
+            input,
+            &declaration.value,
+            "An opcode must be a single byte.",
+        ));
+    };
+
+    let mut custom = CustomOpcode {
+        name: declaration.name_str().to_owned(),
+        opcode,
+        inputs: 0,
+        outputs: 0,
+    };
+    for arg in &r_attribute.args[1..] {
+        let RAttributeArg::AttributeEquality(eq) = &arg.inner else {
+            return Err(new_error_from_located(
+                input,
+                arg,
+                &format!("Expected `inputs` or `outputs` - {}", OPCODE_EXAMPLE),
+            ));
+        };
+        let count = match eq.name_str() {
+            "inputs" => &mut custom.inputs,
+            "outputs" => &mut custom.outputs,
+            _ => {
+                return Err(new_error_from_located(
+                    input,
+                    &eq.name,
+                    &format!("Expected `inputs` or `outputs` - {}", OPCODE_EXAMPLE),
+                ));
+            }
+        };
+        let Some(value) = number_value(input, &eq.value, compile_variables)?
+            .to_usize()
+            .filter(|value| *value <= 1024)
+        else {
+            return Err(new_error_from_located(
+                input,
+                &eq.value,
+                "The stack holds at most 1024 items.",
+            ));
+        };
+        *count = value;
+    }
+
+    Ok(custom)
+}
+
+pub fn number_value(
+    input: &Sources,
+    value: &Located<RAttributeEqualityRight>,
+    compile_variables: &HashMap<String, Bytes>,
+) -> Result<Bytes32, PestError> {
+    let bytes = match &value.inner {
+        RAttributeEqualityRight::HexLiteral(hex_literal) => hex_literal.0.clone(),
+        RAttributeEqualityRight::DecimalLiteral(decimal_literal) => {
+            return Ok(decimal_literal.0.clone());
+        }
+        RAttributeEqualityRight::CompileVariable(compile_variable) => {
+            get_compile_variable_value(input, compile_variable, compile_variables)?.clone()
+        }
+        RAttributeEqualityRight::StringLiteral(_) => {
+            return Err(new_error_from_located(input, value, "Expected a number."));
+        }
+    };
+    match Bytes32::from_bytes(&bytes, true) {
+        Some(value) => Ok(value),
+        None => Err(new_error_from_located(
+            input,
+            value,
+            "Literal exceeds 32 bytes.",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compile::file::compile_source;
+
+    #[test]
+    fn test_custom_opcode() {
+        for declaration in ["#![opcode", "#[opcode"] {
+            let artifacts = compile_source(
+                &format!(
+                    "{}(l1blocknumber = 0x4b, inputs = 0, outputs = 1)]
+                    contract C {{
+                        block main {{ l1blocknumber pop stop }}
+                    }}",
+                    declaration
+                ),
+                "C",
+            )
+            .unwrap();
+            assert_eq!(hex::encode(artifacts.main_bytecode()), "4b5000");
+        }
+
+        let err = compile_source(
+            "#[opcode(add = 0x4b)]
+            contract C {
+                block main { stop }
+            }",
+            "C",
+        )
+        .unwrap_err();
+        assert!(err.contains("Name `add` already used"), "{}", err);
+
+        let err = compile_source(
+            "#![opcode(foo = 0x4b)]
+            contract C {
+                block main { stop }
+                block foo { stop }
+            }",
+            "C",
+        )
+        .unwrap_err();
+        assert!(err.contains("main.mep:4:23"), "{}", err);
+        assert!(err.contains("Name `foo` already used"), "{}", err);
+
+        // the opcodes of a contract excluded by `cfg` are not declared
+        let artifacts = compile_source(
+            "#[cfg($TESTNET$)]
+            #[opcode(l1blocknumber = 0x4c, inputs = 0, outputs = 1)]
+            contract Testnet {
+                block main { stop }
+            }
+
+            #[cfg(not($TESTNET$))]
+            #[opcode(l1blocknumber = 0x4b, inputs = 0, outputs = 1)]
+            contract C {
+                block main { l1blocknumber pop stop }
+            }",
+            "C",
+        )
+        .unwrap();
+        assert_eq!(hex::encode(artifacts.main_bytecode()), "4b5000");
+
+        // after the last contract
+        for declaration in ["#![opcode", "#[opcode"] {
+            let artifacts = compile_source(
+                &format!(
+                    "contract C {{
+                        block main {{ l1blocknumber pop stop }}
+                    }}
+                    {}(l1blocknumber = 0x4b, inputs = 0, outputs = 1)]",
+                    declaration
+                ),
+                "C",
+            )
+            .unwrap();
+            assert_eq!(hex::encode(artifacts.main_bytecode()), "4b5000");
+        }
+    }
+}
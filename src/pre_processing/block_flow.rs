@@ -1,16 +1,24 @@
 use std::collections::{HashMap, HashSet};
 
-use bytes::{BufMut, Bytes, BytesMut};
+use bytes::{Bytes, BytesMut};
 use indexmap::IndexSet;
 
 use super::attribute::Attribute;
-use super::opcode::str_to_op;
-use super::pre_processing::get_compile_variable_value;
+use super::evm_version::{EvmVersion, check_evm_version};
+use super::expression::{Expression, Value, build_expression, evaluate_expression};
+use super::opcode::{Opcodes, str_to_op};
+use super::pre_processing::{Placeholder, get_compile_variable_value};
 use crate::ast::*;
-use crate::parser::error::{PestError, new_error_from_located};
+use crate::compile::artifacts::ADDRESS_LENGTH;
+use crate::parser::error::{PestError, new_error_from_located, new_error_from_location};
 use crate::parser::parser::{Located, Location};
+use crate::parser::source::Sources;
 use crate::types::bytes32::Bytes32;
 
+/// Maximum number of items of a block once its `repeat`s are unrolled, the size limit of a
+/// deployment code.
+const MAX_UNROLLED_ITEMS: usize = 0xc000;
+
 #[derive(Clone, Debug)]
 pub struct BlockFlow {
     pub items: Vec<BlockFlowItem>,
@@ -19,13 +27,43 @@ pub struct BlockFlow {
     pub weak_deps: IndexSet<usize>,
 }
 
+/// Labels declared inside the blocks of a contract, identified by their index.
+#[derive(Clone, Debug, Default)]
+pub struct Labels {
+    /// Index of the labels of each block, by name.
+    pub by_block: Vec<HashMap<String, usize>>,
+    /// Whether each label is declared inside an abstract block.
+    pub in_abstract: Vec<bool>,
+}
+
 #[derive(Clone, Debug)]
 pub enum BlockFlowItem {
-    Bytes(Bytes),
+    Label(BlockFlowLabel),
+    Bytes(Located<Bytes>),
     Contract(usize),
+    Initcode(usize),
     BlockEsp(BlockFlowBlockRef),
     BlockStar(BlockFlowBlockRef),
     Push(BlockFlowPush),
+    TableEntry(BlockFlowTableEntry),
+    /// `dup` or `swap` of a named item of the stack, resolved once all the blocks are analyzed.
+    StackSlot(BlockFlowStackSlot),
+    /// Names of the items on top of the stack at this point, the first being the top.
+    StackLayout(Located<Vec<String>>),
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockFlowStackSlot {
+    pub name: String,
+    pub swap: bool,
+    pub location: Location,
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockFlowLabel {
+    pub index: usize,
+    pub name: String,
+    pub location: Location,
 }
 
 #[derive(Clone, Debug)]
@@ -33,12 +71,23 @@ pub struct BlockFlowBlockRef {
     pub index: usize,
     pub location: Location,
     pub attributes: Vec<Attribute>,
+    pub args: Vec<Located<Expression<BlockFlowPushInner>>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockFlowTableEntry {
+    pub inner: BlockFlowPushInner,
+    pub width: usize,
+    pub location: Location,
 }
 
 #[derive(Clone, Debug)]
 pub struct BlockFlowPush {
     pub inner: BlockFlowPushInner,
+    pub location: Location,
     pub attributes: Vec<Attribute>,
+    /// Exact number of bytes pushed, given by `pushN` or the attribute `width`.
+    pub width: Option<Located<usize>>,
 }
 
 #[derive(Clone, Debug)]
@@ -46,32 +95,131 @@ pub enum BlockFlowPushInner {
     Constant(Bytes32),
     BlockPc(usize),
     BlockSize(usize),
+    LabelPc(usize, Location),
+    /// Index of a parameter of the abstract block, substituted at each expansion.
+    Parameter(usize),
+    /// Expression over block offsets and parameters, evaluated once the contract is laid out.
+    Expression(Box<Located<Expression<BlockFlowPushInner>>>),
+    /// Immutable of the contract, pushed as zeros to be patched at deployment.
+    Immutable {
+        name: String,
+        width: usize,
+    },
+    /// Address of a library, pushed as zeros to be patched once it is deployed.
+    Link(String),
+    /// Size of the deployment code of another contract, known once it is compiled.
+    InitcodeSize(usize),
+    /// Offsets of the references to an immutable of another contract, known once it is compiled.
+    ImmutableOffsets {
+        contract_index: usize,
+        name: String,
+        location: Location,
+    },
+}
+
+impl BlockFlowPushInner {
+    /// Whether the push is patched after the compilation, or depends on another contract.
+    pub fn must_be_pushed_directly(&self) -> bool {
+        matches!(
+            self,
+            Self::Immutable { .. }
+                | Self::Link(_)
+                | Self::InitcodeSize(_)
+                | Self::ImmutableOffsets { .. }
+        )
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn analyze_block_flow(
-    input: &str,
+    input: &Sources,
     r_block_with_attr: &Located<WithAttributes<Located<RBlock>>>,
-    constants: &HashMap<String, Bytes>,
+    constants: &HashMap<String, Value>,
+    placeholders: &HashMap<String, Placeholder>,
     contract_names: &HashMap<String, usize>,
     block_names: &HashMap<String, usize>,
+    labels: &Labels,
     contract_dependencies: &mut HashSet<usize>,
+    evm_version: EvmVersion,
+    opcodes: &Opcodes,
     compile_variables: &HashMap<String, Bytes>,
 ) -> Result<BlockFlow, PestError> {
     // tracing::info!("Analyzing flow block {}", r_block_with_attr.inner().name_str());
 
     let r_block = r_block_with_attr.inner();
+    let no_repeat_values = HashMap::new();
+    let mut scope = ExpressionScope {
+        block_index: *block_names.get(r_block.name_str()).unwrap(),
+        params: &r_block.params,
+        repeat_values: &no_repeat_values,
+        constants,
+        placeholders,
+        contract_names,
+        block_names,
+        labels,
+    };
+
+    let mut unrolled_items = Vec::new();
+    unroll_items(
+        input,
+        &r_block.items,
+        &scope,
+        opcodes,
+        compile_variables,
+        &mut unrolled_items,
+    )?;
+    if unrolled_items
+        .iter()
+        .all(|(r_item, _)| matches!(r_item.inner().inner, RBlockItem::Label(_)))
+    {
+        return Err(new_error_from_located(
+            input,
+            &r_block.name,
+            "A block must not be empty.",
+        ));
+    }
 
     let mut items = Vec::<BlockFlowItem>::new();
-    let mut current_bytes: Option<BytesMut> = None;
     let mut current_attributes = Vec::<Attribute>::new();
 
     let mut strong_deps = IndexSet::<usize>::new();
     let mut weak_deps = IndexSet::<usize>::new();
 
-    for r_item_with_attr in &r_block.items {
+    for (r_item_with_attr, repeat_values) in &unrolled_items {
+        scope.repeat_values = repeat_values;
+        if r_block.data {
+            check_data_item(input, r_item_with_attr, &scope)?;
+        }
+
+        let mut width: Option<Located<usize>> = None;
         for r_attribute in &r_item_with_attr.attributes {
-            let attribute = Attribute::from_r_attribute(input, r_attribute, compile_variables)?;
-            if attribute.is_block_item_attribute() {
+            let attribute =
+                Attribute::from_r_attribute(input, r_attribute, evm_version, compile_variables)?;
+            if let Attribute::Stack(annotation) = attribute {
+                if annotation.inputs.is_some() || annotation.outputs.is_some() {
+                    return Err(new_error_from_located(
+                        input,
+                        r_attribute,
+                        "Only the names of the items on the stack can be declared on a line - ex: #[stack(a, b)]",
+                    ));
+                }
+                items.push(BlockFlowItem::StackLayout(Located {
+                    location: annotation.location,
+                    inner: annotation.layout,
+                }));
+            } else if let Attribute::Width(n) = attribute {
+                if !matches!(r_item_with_attr.inner().inner, RBlockItem::Function(_)) {
+                    return Err(new_error_from_located(
+                        input,
+                        r_attribute,
+                        "The attribute `width` only applies to a push.",
+                    ));
+                }
+                width = Some(Located {
+                    location: r_item_with_attr.inner().location.clone(),
+                    inner: n,
+                });
+            } else if attribute.is_block_item_attribute() {
                 current_attributes.push(attribute);
             } else {
                 return Err(new_error_from_located(
@@ -85,16 +233,34 @@ pub fn analyze_block_flow(
         let r_item = r_item_with_attr.inner();
 
         if let RBlockItem::HexAlias(hex_alias) = &r_item.inner {
-            match hex_alias {
-                RHexAlias::HexLiteral(hex_literal) => {
-                    append_or_create_bytes(&mut current_bytes, &hex_literal.0);
+            let bytes = match hex_alias {
+                RHexAlias::HexLiteral(hex_literal) => hex_literal.0.clone(),
+                RHexAlias::StringLiteral(string_literal) => {
+                    Bytes::copy_from_slice(string_literal.as_str().as_bytes())
                 }
                 RHexAlias::Variable(variable) => {
                     let variable_name = variable.as_str();
                     if let Some(op) = str_to_op(variable_name) {
-                        push_or_create_bytes(&mut current_bytes, op);
+                        if let Err(err) = check_evm_version(op, variable_name, evm_version) {
+                            return Err(new_error_from_located(input, r_item, &err));
+                        }
+                        Bytes::copy_from_slice(&[op])
+                    } else if let Some(custom_opcode) = opcodes.custom(variable_name) {
+                        Bytes::copy_from_slice(&[custom_opcode.opcode])
                     } else if let Some(constant) = constants.get(variable_name) {
-                        append_or_create_bytes(&mut current_bytes, constant);
+                        constant.bytes.clone()
+                    } else if placeholders.contains_key(variable_name)
+                        || repeat_values.contains_key(variable_name)
+                        || r_block
+                            .params
+                            .iter()
+                            .any(|param| param.as_str() == variable_name)
+                    {
+                        return Err(new_error_from_located(
+                            input,
+                            r_item,
+                            &format!("`{}` can only be used inside a push.", variable_name),
+                        ));
                     } else {
                         return Err(new_error_from_located(
                             input,
@@ -104,21 +270,28 @@ pub fn analyze_block_flow(
                     }
                 }
                 RHexAlias::CompileVariable(compile_variable) => {
-                    append_or_create_bytes(
-                        &mut current_bytes,
-                        get_compile_variable_value(input, compile_variable, compile_variables)?,
-                    );
+                    get_compile_variable_value(input, compile_variable, compile_variables)?.clone()
                 }
-            }
+            };
+            items.push(BlockFlowItem::Bytes(Located {
+                location: r_item.location.clone(),
+                inner: bytes,
+            }));
             continue;
         }
 
-        if let Some(c_bytes) = current_bytes.take() {
-            items.push(BlockFlowItem::Bytes(c_bytes.into()));
-        }
-
         match &r_item.inner {
-            RBlockItem::HexAlias(_) => unreachable!(),
+            RBlockItem::HexAlias(_) | RBlockItem::Repeat(_) => unreachable!(),
+            RBlockItem::Label(label) => {
+                let label_index = *labels.by_block[scope.block_index]
+                    .get(label.name_str())
+                    .unwrap();
+                items.push(BlockFlowItem::Label(BlockFlowLabel {
+                    index: label_index,
+                    name: label.name_str().to_owned(),
+                    location: r_item.location.clone(),
+                }));
+            }
             RBlockItem::BlockRef(RBlockRef::Star(RBlockRefStar::Variable(variable))) => {
                 let block_name = variable.as_str();
                 let Some(block_index) = block_names.get(block_name) else {
@@ -134,6 +307,7 @@ pub fn analyze_block_flow(
                     index: *block_index,
                     location: r_item.location.clone(),
                     attributes: current_attributes,
+                    args: Vec::new(),
                 }));
                 current_attributes = Vec::new();
             }
@@ -153,15 +327,53 @@ pub fn analyze_block_flow(
                         index: *block_index,
                         location: r_item.location.clone(),
                         attributes: current_attributes,
+                        args: Vec::new(),
+                    }));
+                    current_attributes = Vec::new();
+                }
+                RBlockRefEsp::Call(block_call) => {
+                    let block_name = block_call.name.as_str();
+                    let Some(block_index) = block_names.get(block_name) else {
+                        return Err(new_error_from_located(
+                            input,
+                            r_item,
+                            &format!("Block `{}` not found in this contract.", block_name),
+                        ));
+                    };
+
+                    let mut args = Vec::with_capacity(block_call.args.len());
+                    for arg in &block_call.args {
+                        args.push(analyze_block_call_arg(
+                            input,
+                            arg,
+                            &scope,
+                            &mut weak_deps,
+                            contract_dependencies,
+                            compile_variables,
+                        )?);
+                    }
+
+                    strong_deps.insert(*block_index);
+                    items.push(BlockFlowItem::BlockEsp(BlockFlowBlockRef {
+                        index: *block_index,
+                        location: r_item.location.clone(),
+                        attributes: current_attributes,
+                        args,
                     }));
                     current_attributes = Vec::new();
                 }
                 RBlockRefEsp::VariableWithField(variable_with_field) => {
-                    let field_name = variable_with_field.field.as_str();
-                    if field_name != "code" {
+                    let field = variable_with_field
+                        .path
+                        .first()
+                        .unwrap_or(&variable_with_field.field);
+                    let field_name = field.as_str();
+                    if !matches!(field_name, "code" | "initcode")
+                        || !variable_with_field.path.is_empty()
+                    {
                         return Err(new_error_from_located(
                             input,
-                            &variable_with_field.field,
+                            field,
                             &format!("Unknown field {}.", field_name),
                         ));
                     }
@@ -176,169 +388,154 @@ pub fn analyze_block_flow(
                         ));
                     };
 
-                    items.push(BlockFlowItem::Contract(*contract_index));
+                    items.push(if field_name == "code" {
+                        BlockFlowItem::Contract(*contract_index)
+                    } else {
+                        BlockFlowItem::Initcode(*contract_index)
+                    });
                     contract_dependencies.insert(*contract_index);
                 }
             },
+            RBlockItem::TableEntry(expression) => {
+                // a block name is a shorthand for its pc
+                let inner = match expression {
+                    RExpression::Variable(variable)
+                        if block_names.contains_key(variable.as_str()) =>
+                    {
+                        let block_index = *block_names.get(variable.as_str()).unwrap();
+                        weak_deps.insert(block_index);
+                        BlockFlowPushInner::BlockPc(block_index)
+                    }
+                    _ => analyze_function_arg(
+                        input,
+                        &Located {
+                            location: r_item.location.clone(),
+                            inner: expression.clone(),
+                        },
+                        true,
+                        &scope,
+                        &mut weak_deps,
+                        contract_dependencies,
+                        compile_variables,
+                    )?,
+                };
+                if matches!(
+                    inner,
+                    BlockFlowPushInner::Immutable { .. } | BlockFlowPushInner::Link(_)
+                ) {
+                    return Err(new_error_from_located(
+                        input,
+                        r_item,
+                        "Immutables and links cannot be table entries.",
+                    ));
+                }
+
+                items.push(BlockFlowItem::TableEntry(BlockFlowTableEntry {
+                    inner,
+                    width: r_block.table_width.unwrap(),
+                    location: r_item.location.clone(),
+                }));
+            }
+            RBlockItem::Function(function)
+                if matches!(
+                    function.name.as_str().to_lowercase().as_str(),
+                    "dup" | "swap"
+                ) =>
+            {
+                let RExpression::Variable(name) = &function.arg.inner else {
+                    return Err(new_error_from_located(
+                        input,
+                        &function.arg,
+                        "Expected the name of an item of the stack - ex: dup(a)",
+                    ));
+                };
+                if let Some(width) = width {
+                    return Err(new_error_from_located(
+                        input,
+                        &width,
+                        "The attribute `width` only applies to a push.",
+                    ));
+                }
+                items.push(BlockFlowItem::StackSlot(BlockFlowStackSlot {
+                    name: name.as_str().to_owned(),
+                    swap: function.name.as_str().eq_ignore_ascii_case("swap"),
+                    location: r_item.location.clone(),
+                }));
+            }
             RBlockItem::Function(function) => {
                 let function_name = function.name.as_str();
 
                 let push_right = match function_name.to_lowercase().as_str() {
                     "push" | "rpush" => true,
                     "lpush" => false,
-                    _ => {
-                        return Err(new_error_from_located(
-                            input,
-                            &function.name,
-                            &format!("Unknown function `{}`.", function_name),
-                        ));
-                    }
-                };
-
-                let push = match &function.arg.inner {
-                    RFunctionArg::HexAlias(RHexAlias::HexLiteral(hex_literal)) => {
-                        let Some(formatted) = Bytes32::from_bytes(&hex_literal.0, push_right)
-                        else {
-                            return Err(new_error_from_located(
-                                input,
-                                &function.arg,
-                                "Push content exceeds 32 bytes.",
-                            ));
-                        };
-
-                        BlockFlowPushInner::Constant(formatted)
-                    }
-                    RFunctionArg::HexAlias(RHexAlias::Variable(variable)) => {
-                        let Some(constant_value) = constants.get(variable.as_str()) else {
-                            return Err(new_error_from_located(
-                                input,
-                                &function.arg,
-                                &format!("Unknown argument `{}`.", variable.as_str()),
-                            ));
-                        };
-
-                        let Some(formatted) = Bytes32::from_bytes(constant_value, push_right)
-                        else {
-                            return Err(new_error_from_located(
-                                input,
-                                &function.arg,
-                                "Push content exceeds 32 bytes.",
-                            ));
-                        };
-
-                        BlockFlowPushInner::Constant(formatted)
-                    }
-                    RFunctionArg::HexAlias(RHexAlias::CompileVariable(compile_variable)) => {
-                        let bytes =
-                            get_compile_variable_value(input, compile_variable, compile_variables)?;
-                        let Some(formatted) = Bytes32::from_bytes(bytes, push_right) else {
+                    name => {
+                        let Some(n) = explicit_push_width(name) else {
                             return Err(new_error_from_located(
                                 input,
-                                &function.arg,
-                                "Push content exceeds 32 bytes.",
+                                &function.name,
+                                &format!("Unknown function `{}`.", function_name),
                             ));
                         };
-
-                        BlockFlowPushInner::Constant(formatted)
-                    }
-                    RFunctionArg::VariableWithField(variable_with_field) => {
-                        if !push_right {
+                        if width.as_ref().is_some_and(|width| width.inner != n) {
                             return Err(new_error_from_located(
                                 input,
-                                &variable_with_field.variable,
-                                "Left push can only take constants as argument.",
+                                &function.name,
+                                "The width of this push is already given by the attribute `width`.",
                             ));
                         }
-
-                        let field_name = variable_with_field.field.as_str();
-                        let variable_name = variable_with_field.variable.as_str();
-                        match field_name {
-                            "pc" => {
-                                if let Some(block_index) = block_names.get(variable_name) {
-                                    weak_deps.insert(*block_index);
-                                    BlockFlowPushInner::BlockPc(*block_index)
-                                } else {
-                                    return Err(new_error_from_located(
-                                        input,
-                                        &variable_with_field.variable,
-                                        &format!("Block `{}` not found.", variable_name),
-                                    ));
-                                }
-                            }
-                            "size" => {
-                                if let Some(block_index) = block_names.get(variable_name) {
-                                    weak_deps.insert(*block_index);
-                                    BlockFlowPushInner::BlockSize(*block_index)
-                                } else {
-                                    return Err(new_error_from_located(
-                                        input,
-                                        &variable_with_field.variable,
-                                        &format!("Block `{}` not found.", variable_name),
-                                    ));
-                                }
-                            }
-                            _ => {
-                                return Err(new_error_from_located(
-                                    input,
-                                    &variable_with_field.field,
-                                    &format!("Unknown field `{}`.", field_name),
-                                ));
-                            }
-                        }
+                        width = Some(Located {
+                            location: r_item.location.clone(),
+                            inner: n,
+                        });
+                        true
                     }
-                    RFunctionArg::VariablesConcat(concat) => {
-                        let mut bytes = BytesMut::new();
-                        for variable in &concat.0 {
-                            let value = match &variable.inner {
-                                RHexAlias::Variable(variable) => {
-                                    let Some(constant_value) = constants.get(variable.as_str())
-                                    else {
-                                        return Err(new_error_from_located(
-                                            input,
-                                            &function.arg,
-                                            &format!("Unknown argument `{}`.", variable.as_str()),
-                                        ));
-                                    };
-                                    constant_value
-                                }
-                                RHexAlias::HexLiteral(hex_literal) => &hex_literal.0,
-                                RHexAlias::CompileVariable(compile_variable) => {
-                                    get_compile_variable_value(
-                                        input,
-                                        compile_variable,
-                                        compile_variables,
-                                    )?
-                                }
-                            };
-
-                            bytes.extend_from_slice(value);
-                        }
-
-                        let Some(formatted) = Bytes32::from_bytes(&bytes.into(), push_right) else {
-                            return Err(new_error_from_located(
-                                input,
-                                &function.arg,
-                                "Push content exceeds 32 bytes.",
-                            ));
-                        };
+                };
 
-                        BlockFlowPushInner::Constant(formatted)
+                // strings are left-aligned, unless pushed with `rpush`
+                let push = match &function.arg.inner {
+                    RExpression::StringLiteral(string_literal)
+                        if !function_name.eq_ignore_ascii_case("rpush") =>
+                    {
+                        string_push(input, &function.arg, string_literal, width.as_ref())?
                     }
+                    _ => analyze_function_arg(
+                        input,
+                        &function.arg,
+                        push_right,
+                        &scope,
+                        &mut weak_deps,
+                        contract_dependencies,
+                        compile_variables,
+                    )?,
                 };
 
+                // immutables and links are always pushed on their own width
+                let fixed_width = match &push {
+                    BlockFlowPushInner::Immutable { width, .. } => Some(*width),
+                    BlockFlowPushInner::Link(_) => Some(ADDRESS_LENGTH),
+                    _ => None,
+                };
+                if let Some(fixed_width) = fixed_width {
+                    if width.take().is_some_and(|width| width.inner != fixed_width) {
+                        return Err(new_error_from_located(
+                            input,
+                            r_item,
+                            &format!("This value is pushed on {} bytes.", fixed_width),
+                        ));
+                    }
+                }
+
                 items.push(BlockFlowItem::Push(BlockFlowPush {
                     inner: push,
+                    location: r_item.location.clone(),
                     attributes: current_attributes,
+                    width,
                 }));
                 current_attributes = Vec::new();
             }
         }
     }
 
-    if let Some(c_bytes) = current_bytes.take() {
-        items.push(BlockFlowItem::Bytes(c_bytes.into()));
-    }
-
     Ok(BlockFlow {
         items,
         end_attributes: current_attributes,
@@ -347,24 +544,712 @@ pub fn analyze_block_flow(
     })
 }
 
-pub fn append_or_create_bytes(current_bytes: &mut Option<BytesMut>, new_bytes: &Bytes) {
-    if let Some(c_bytes) = current_bytes.as_mut() {
-        c_bytes.extend_from_slice(new_bytes);
-    } else {
-        current_bytes.replace(new_bytes[..].into());
+/// Data blocks only hold literals, constants, compile variables and the code of contracts.
+fn check_data_item(
+    input: &Sources,
+    r_item_with_attr: &Located<WithAttributes<Located<RBlockItem>>>,
+    scope: &ExpressionScope,
+) -> Result<(), PestError> {
+    if let Some(r_attribute) = r_item_with_attr.attributes.first() {
+        return Err(new_error_from_located(
+            input,
+            r_attribute,
+            "Attributes cannot be applied inside a data block.",
+        ));
     }
+
+    let r_item = r_item_with_attr.inner();
+    let is_data = match &r_item.inner {
+        RBlockItem::HexAlias(RHexAlias::Variable(variable)) => {
+            scope.constants.contains_key(variable.as_str())
+        }
+        RBlockItem::HexAlias(_)
+        | RBlockItem::BlockRef(RBlockRef::Esp(RBlockRefEsp::VariableWithField(_))) => true,
+        _ => false,
+    };
+    if !is_data {
+        return Err(new_error_from_located(
+            input,
+            r_item,
+            "A data block only holds literals, constants, compile variables and contract codes.",
+        ));
+    }
+    Ok(())
 }
 
-pub fn push_or_create_bytes(current_bytes: &mut Option<BytesMut>, new_byte: u8) {
-    if let Some(c_bytes) = current_bytes.as_mut() {
-        c_bytes.put_u8(new_byte);
-    } else {
-        let mut c_bytes = BytesMut::new();
-        c_bytes.put_u8(new_byte);
-        current_bytes.replace(c_bytes);
+/// Item of a block, with the values of the variables of the `repeat`s containing it.
+type UnrolledItem<'a> = (
+    &'a Located<WithAttributes<Located<RBlockItem>>>,
+    HashMap<String, Value>,
+);
+
+/// Expands the `repeat`s of a block, whose bounds are evaluated from the constants and the
+/// variables of the enclosing `repeat`s.
+fn unroll_items<'a>(
+    input: &Sources,
+    r_items: &'a [Located<WithAttributes<Located<RBlockItem>>>],
+    scope: &ExpressionScope,
+    opcodes: &Opcodes,
+    compile_variables: &HashMap<String, Bytes>,
+    unrolled_items: &mut Vec<UnrolledItem<'a>>,
+) -> Result<(), PestError> {
+    for r_item_with_attr in r_items {
+        let r_item = r_item_with_attr.inner();
+        let r_repeat = match &r_item.inner {
+            RBlockItem::Repeat(r_repeat) => r_repeat,
+            RBlockItem::Label(_) if !scope.repeat_values.is_empty() => {
+                return Err(new_error_from_located(
+                    input,
+                    r_item,
+                    "Labels cannot be declared inside a `repeat`.",
+                ));
+            }
+            _ => {
+                unrolled_items.push((r_item_with_attr, scope.repeat_values.clone()));
+                continue;
+            }
+        };
+
+        if let Some(r_attribute) = r_item_with_attr.attributes.first() {
+            return Err(new_error_from_located(
+                input,
+                r_attribute,
+                "Attributes cannot be applied to a `repeat`.",
+            ));
+        }
+
+        let variable_name = r_repeat.variable.as_str();
+        if str_to_op(variable_name).is_some()
+            || opcodes.custom(variable_name).is_some()
+            || is_function_name(variable_name)
+            || scope
+                .params
+                .iter()
+                .any(|param| param.as_str() == variable_name)
+            || scope.repeat_values.contains_key(variable_name)
+            || scope.constants.contains_key(variable_name)
+            || scope.placeholders.contains_key(variable_name)
+            || scope.contract_names.contains_key(variable_name)
+            || scope.block_names.contains_key(variable_name)
+        {
+            return Err(new_error_from_located(
+                input,
+                &r_repeat.variable,
+                &format!("Name `{}` already used", variable_name),
+            ));
+        }
+
+        let start = repeat_bound(input, &r_repeat.start, scope, compile_variables)?;
+        let end = repeat_bound(input, &r_repeat.end, scope, compile_variables)?;
+        let too_many_items = || {
+            let range = Location {
+                end: r_repeat.end.location.end,
+                ..r_repeat.start.location.clone()
+            };
+            new_error_from_location(
+                input,
+                &range,
+                &format!(
+                    "The block has more than {} items once this `repeat` is unrolled.",
+                    MAX_UNROLLED_ITEMS
+                ),
+            )
+        };
+        if end.saturating_sub(start) > MAX_UNROLLED_ITEMS {
+            return Err(too_many_items());
+        }
+        for value in start..end {
+            let mut repeat_values = scope.repeat_values.clone();
+            repeat_values.insert(
+                variable_name.to_owned(),
+                Value::from_word(Bytes32::from_usize(value)),
+            );
+            let inner_scope = ExpressionScope {
+                repeat_values: &repeat_values,
+                ..*scope
+            };
+            unroll_items(
+                input,
+                &r_repeat.items,
+                &inner_scope,
+                opcodes,
+                compile_variables,
+                unrolled_items,
+            )?;
+            if unrolled_items.len() > MAX_UNROLLED_ITEMS {
+                return Err(too_many_items());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn repeat_bound(
+    input: &Sources,
+    bound: &Located<RExpression>,
+    scope: &ExpressionScope,
+    compile_variables: &HashMap<String, Bytes>,
+) -> Result<usize, PestError> {
+    let value = evaluate_expression(
+        input,
+        bound,
+        &mut |name, location| {
+            if let Some(value) = scope
+                .repeat_values
+                .get(name)
+                .or_else(|| scope.constants.get(name))
+            {
+                Ok(value.clone())
+            } else {
+                Err(new_error_from_location(
+                    input,
+                    location,
+                    &format!("Unknown constant `{}`.", name),
+                ))
+            }
+        },
+        compile_variables,
+    )?;
+    match Bytes32::from_bytes(&value.bytes, true).and_then(|word| word.to_usize()) {
+        Some(bound) if !value.negative => Ok(bound),
+        _ => Err(new_error_from_located(
+            input,
+            bound,
+            "The bounds of a `repeat` must be unsigned numbers.",
+        )),
+    }
+}
+
+/// Names that can be referenced by the expressions of a block.
+struct ExpressionScope<'a> {
+    block_index: usize,
+    params: &'a [Located<RVariable>],
+    /// Values of the variables of the `repeat`s containing the current item.
+    repeat_values: &'a HashMap<String, Value>,
+    constants: &'a HashMap<String, Value>,
+    placeholders: &'a HashMap<String, Placeholder>,
+    contract_names: &'a HashMap<String, usize>,
+    block_names: &'a HashMap<String, usize>,
+    labels: &'a Labels,
+}
+
+fn analyze_function_arg(
+    input: &Sources,
+    arg: &Located<RExpression>,
+    push_right: bool,
+    scope: &ExpressionScope,
+    weak_deps: &mut IndexSet<usize>,
+    contract_deps: &mut HashSet<usize>,
+    compile_variables: &HashMap<String, Bytes>,
+) -> Result<BlockFlowPushInner, PestError> {
+    let expression = analyze_expression(
+        input,
+        arg,
+        scope,
+        weak_deps,
+        contract_deps,
+        compile_variables,
+    )?;
+
+    Ok(match expression.inner {
+        Expression::Value(value) => {
+            let Some(formatted) = Bytes32::from_bytes(&value.bytes, push_right) else {
+                return Err(new_error_from_located(
+                    input,
+                    arg,
+                    "Push content exceeds 32 bytes.",
+                ));
+            };
+            BlockFlowPushInner::Constant(formatted)
+        }
+        _ if !push_right => {
+            return Err(new_error_from_located(
+                input,
+                arg,
+                "Left push can only take constants as argument.",
+            ));
+        }
+        Expression::Leaf(leaf) => leaf,
+        _ => BlockFlowPushInner::Expression(Box::new(expression)),
+    })
+}
+
+/// Pushes a string left-aligned on 32 bytes, or on the explicit width of the push.
+fn string_push(
+    input: &Sources,
+    arg: &Located<RExpression>,
+    string_literal: &RStringLiteral,
+    width: Option<&Located<usize>>,
+) -> Result<BlockFlowPushInner, PestError> {
+    let mut bytes = BytesMut::from(string_literal.as_str().as_bytes());
+    let push_right = match width {
+        Some(width) => {
+            if bytes.len() < width.inner {
+                bytes.resize(width.inner, 0x00);
+            }
+            true
+        }
+        None => false,
+    };
+    let Some(formatted) = Bytes32::from_bytes(&bytes.freeze(), push_right) else {
+        return Err(new_error_from_located(
+            input,
+            arg,
+            "Push content exceeds 32 bytes.",
+        ));
+    };
+    Ok(BlockFlowPushInner::Constant(formatted))
+}
+
+fn analyze_block_call_arg(
+    input: &Sources,
+    arg: &Located<RExpression>,
+    scope: &ExpressionScope,
+    weak_deps: &mut IndexSet<usize>,
+    contract_deps: &mut HashSet<usize>,
+    compile_variables: &HashMap<String, Bytes>,
+) -> Result<Located<Expression<BlockFlowPushInner>>, PestError> {
+    let expression = analyze_expression(
+        input,
+        arg,
+        scope,
+        weak_deps,
+        contract_deps,
+        compile_variables,
+    )?;
+
+    if let Expression::Value(value) = &expression.inner {
+        if value.bytes.len() > 32 {
+            return Err(new_error_from_located(
+                input,
+                arg,
+                "Push content exceeds 32 bytes.",
+            ));
+        }
+    }
+
+    Ok(expression)
+}
+
+/// Builds the expression of a push argument, whose leaves are block offsets, labels, immutables,
+/// links and parameters.
+fn analyze_expression(
+    input: &Sources,
+    arg: &Located<RExpression>,
+    scope: &ExpressionScope,
+    weak_deps: &mut IndexSet<usize>,
+    contract_deps: &mut HashSet<usize>,
+    compile_variables: &HashMap<String, Bytes>,
+) -> Result<Located<Expression<BlockFlowPushInner>>, PestError> {
+    build_expression(
+        input,
+        arg,
+        &mut |name, location| {
+            if let Some(param_index) = scope.params.iter().position(|param| param.as_str() == name)
+            {
+                Ok(Expression::Leaf(BlockFlowPushInner::Parameter(param_index)))
+            } else if let Some(value) = scope.repeat_values.get(name) {
+                Ok(Expression::Value(value.clone()))
+            } else if let Some(constant) = scope.constants.get(name) {
+                Ok(Expression::Value(constant.clone()))
+            } else if let Some(placeholder) = scope.placeholders.get(name) {
+                Ok(Expression::Leaf(match placeholder {
+                    Placeholder::Immutable { width } => BlockFlowPushInner::Immutable {
+                        name: name.to_owned(),
+                        width: *width,
+                    },
+                    Placeholder::Link => BlockFlowPushInner::Link(name.to_owned()),
+                }))
+            } else {
+                Err(new_error_from_location(
+                    input,
+                    location,
+                    &format!("Unknown argument `{}`.", name),
+                ))
+            }
+        },
+        &mut |variable_with_field| {
+            analyze_field(input, variable_with_field, scope, weak_deps, contract_deps)
+                .map(Expression::Leaf)
+        },
+        compile_variables,
+    )
+}
+
+/// Resolves `block.pc`, `block.size`, `block.label.pc` and `label.pc`, the latter referring to a
+/// label of the current block, `Contract.initcode.size` and `Contract.immutable.NAME.offsets`.
+fn analyze_field(
+    input: &Sources,
+    variable_with_field: &RVariableWithField,
+    scope: &ExpressionScope,
+    weak_deps: &mut IndexSet<usize>,
+    contract_deps: &mut HashSet<usize>,
+) -> Result<BlockFlowPushInner, PestError> {
+    let field_name = variable_with_field.field.as_str();
+    let variable_name = variable_with_field.variable.as_str();
+
+    if let Some(contract_index) = scope.contract_names.get(variable_name) {
+        let push = match (variable_with_field.path.as_slice(), field_name) {
+            ([kind], "size") if kind.as_str() == "initcode" => {
+                BlockFlowPushInner::InitcodeSize(*contract_index)
+            }
+            ([kind, immutable_name], "offsets") if kind.as_str() == "immutable" => {
+                BlockFlowPushInner::ImmutableOffsets {
+                    contract_index: *contract_index,
+                    name: immutable_name.as_str().to_owned(),
+                    location: immutable_name.location.clone(),
+                }
+            }
+            _ => {
+                return Err(new_error_from_located(
+                    input,
+                    variable_with_field
+                        .path
+                        .first()
+                        .unwrap_or(&variable_with_field.field),
+                    "Expected `initcode.size` or `immutable.NAME.offsets`.",
+                ));
+            }
+        };
+        contract_deps.insert(*contract_index);
+        return Ok(push);
+    }
+
+    let (block_index, label) = match variable_with_field.path.as_slice() {
+        [] => match scope.block_names.get(variable_name) {
+            Some(block_index) => (*block_index, None),
+            None => match scope.labels.by_block[scope.block_index].get(variable_name) {
+                Some(label_index) => (scope.block_index, Some(*label_index)),
+                None => {
+                    return Err(new_error_from_located(
+                        input,
+                        &variable_with_field.variable,
+                        &format!("Block or label `{}` not found.", variable_name),
+                    ));
+                }
+            },
+        },
+        [label_name] => {
+            let Some(block_index) = scope.block_names.get(variable_name) else {
+                return Err(new_error_from_located(
+                    input,
+                    &variable_with_field.variable,
+                    &format!("Block `{}` not found.", variable_name),
+                ));
+            };
+            let Some(label_index) = scope.labels.by_block[*block_index].get(label_name.as_str())
+            else {
+                return Err(new_error_from_located(
+                    input,
+                    label_name,
+                    &format!(
+                        "Label `{}` not found in block `{}`.",
+                        label_name.as_str(),
+                        variable_name
+                    ),
+                ));
+            };
+            (*block_index, Some(*label_index))
+        }
+        [_, unknown, ..] => {
+            return Err(new_error_from_located(
+                input,
+                unknown,
+                &format!("Unknown field `{}`.", unknown.as_str()),
+            ));
+        }
+    };
+
+    match (field_name, label) {
+        ("pc", Some(label_index)) => {
+            // The block of a label declared in an abstract block is placed by its expansion.
+            if !scope.labels.in_abstract[label_index] {
+                weak_deps.insert(block_index);
+            }
+            Ok(BlockFlowPushInner::LabelPc(
+                label_index,
+                variable_with_field.variable.location.clone(),
+            ))
+        }
+        ("pc", None) => {
+            weak_deps.insert(block_index);
+            Ok(BlockFlowPushInner::BlockPc(block_index))
+        }
+        ("size", None) => {
+            weak_deps.insert(block_index);
+            Ok(BlockFlowPushInner::BlockSize(block_index))
+        }
+        _ => Err(new_error_from_located(
+            input,
+            &variable_with_field.field,
+            &format!("Unknown field `{}`.", field_name),
+        )),
     }
 }
 
 pub fn is_function_name(name: &str) -> bool {
-    matches!(name.to_lowercase().as_str(), "push" | "lpush" | "rpush")
+    let name = name.to_lowercase();
+    matches!(name.as_str(), "push" | "lpush" | "rpush" | "dup" | "swap")
+        || explicit_push_width(&name).is_some()
+}
+
+/// Width of the functions `push1` to `push32`.
+fn explicit_push_width(name: &str) -> Option<usize> {
+    let width = name.strip_prefix("push")?;
+    if width.starts_with('0') {
+        return None;
+    }
+    width
+        .parse::<usize>()
+        .ok()
+        .filter(|width| (1..=32).contains(width))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compile::file::compile_source;
+
+    #[test]
+    fn test_block_parameters() {
+        let artifacts = compile_source(
+            "contract C {
+                const SHIFT = 0x10;
+                block main {
+                    callvalue &shift_right(0xe0)
+                    callvalue &shift_right(SHIFT)
+                    &push_dest(end.pc) jump
+                }
+                abstract block shift_right(X) { push(X) shr }
+                abstract block push_dest(DEST) { push(DEST) }
+                block end { jumpdest stop }
+            }",
+            "C",
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(artifacts.main_bytecode()),
+            "3460e01c3460101c600b565b00"
+        );
+
+        let err = compile_source(
+            "contract C {
+                block main { callvalue &shift_right(0x01, 0x02) }
+                abstract block shift_right(X) { push(X) shr }
+            }",
+            "C",
+        )
+        .unwrap_err();
+        assert!(err.contains("main.mep:2:40"), "{}", err);
+        assert!(
+            err.contains("Block `shift_right` takes 1 argument(s) but 2 were given."),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_block_parameters_optimized_per_expansion() {
+        // the second expansion pushes `0x20` with `msize`, assumed only from there
+        let artifacts = compile_source(
+            "contract C {
+                #[assume(msize = 0x00)]
+                block main {
+                    callvalue &shift_right(0x20)
+                    push(0x) push(0x) mstore
+                    #[assume(msize = 0x20)]
+                    callvalue &shift_right(0x20)
+                }
+                abstract block shift_right(SHIFT) { push(SHIFT) shr }
+            }",
+            "C",
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(artifacts.main_bytecode()),
+            "3460201c5f5f5234591c"
+        );
+    }
+
+    #[test]
+    fn test_repeat() {
+        let artifacts = compile_source(
+            "contract C {
+                const WORDS = 3;
+                block main {
+                    repeat(i in 0..WORDS) {
+                        push(i * 0x20) calldataload
+                        push(i * 0x20) mstore
+                    }
+                }
+            }",
+            "C",
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(artifacts.main_bytecode()),
+            "5f355f52602035602052604035604052"
+        );
+
+        // the bounds of an inner `repeat` depend on the variable of the outer one
+        let artifacts = compile_source(
+            "contract C {
+                block main {
+                    repeat(i in 0..2) {
+                        repeat(j in i..2) { &f(i * 0x10 + j) }
+                    }
+                }
+                abstract block f(X) { push(X) pop }
+            }",
+            "C",
+        )
+        .unwrap();
+        assert_eq!(hex::encode(artifacts.main_bytecode()), "5f50600150601150");
+    }
+
+    #[test]
+    fn test_repeat_errors() {
+        let err = compile_source(
+            "contract C {
+                block main { repeat(i in 0..2) { label l: jumpdest } }
+            }",
+            "C",
+        )
+        .unwrap_err();
+        assert!(err.contains("main.mep:2:50"), "{}", err);
+        assert!(
+            err.contains("Labels cannot be declared inside a `repeat`."),
+            "{}",
+            err
+        );
+
+        let err = compile_source(
+            "contract C {
+                block main { repeat(main in 0..2) { caller } }
+            }",
+            "C",
+        )
+        .unwrap_err();
+        assert!(err.contains("main.mep:2:37"), "{}", err);
+        assert!(err.contains("Name `main` already used"), "{}", err);
+
+        for items in [
+            "repeat(i in 0..0x010000000000) { caller }",
+            "repeat(i in 0..0x0100) { repeat(j in 0..0x0100) { caller } }",
+        ] {
+            let err = compile_source(
+                &format!(
+                    "contract C {{
+                        block main {{ {} }}
+                    }}",
+                    items
+                ),
+                "C",
+            )
+            .unwrap_err();
+            assert!(
+                err.contains("The block has more than 49152 items once this `repeat` is unrolled."),
+                "{}",
+                err
+            );
+        }
+        let err = compile_source(
+            "contract C {
+                block main { repeat(i in 0x10..0x100000) { caller } }
+            }",
+            "C",
+        )
+        .unwrap_err();
+        assert!(err.contains("main.mep:2:42"), "{}", err);
+    }
+
+    #[test]
+    fn test_data_block() {
+        let artifacts = compile_source(
+            "contract C {
+                const X = 0xaabb;
+                block main { push(d.size) push(d.pc) push(0x) codecopy }
+                data block d { 0x0102 X \"hi\" }
+                #[last]
+                block end { stop }
+            }",
+            "C",
+        )
+        .unwrap();
+        let contract = artifacts.main_artifacts();
+        assert_eq!(
+            hex::encode(&contract.bytecode),
+            "600660075f39000102aabb6869"
+        );
+        assert_eq!((contract.blocks["d"].pc, contract.blocks["d"].size), (7, 6));
+    }
+
+    #[test]
+    fn test_data_block_errors() {
+        let err = compile_source(
+            "contract C {
+                block main { push(d.pc) pop }
+                data block d { 0x01 caller }
+            }",
+            "C",
+        )
+        .unwrap_err();
+        assert!(err.contains("main.mep:3:37"), "{}", err);
+        assert!(
+            err.contains(
+                "A data block only holds literals, constants, compile variables and contract codes."
+            ),
+            "{}",
+            err
+        );
+
+        let err = compile_source(
+            "contract C {
+                block main { *d }
+                data block d { 0x01 }
+            }",
+            "C",
+        )
+        .unwrap_err();
+        assert!(err.contains("main.mep:2:30"), "{}", err);
+        assert!(
+            err.contains(
+                "A data block cannot be inserted in another block, push its `pc` instead."
+            ),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_strings() {
+        let artifacts = compile_source(
+            r#"contract C {
+                block main {
+                    push("hi") rpush("hi") push2("a") push(0x) push(0x) return
+                    "A\x42\n\u{e9}"
+                }
+            }"#,
+            "C",
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(artifacts.main_bytecode()),
+            // `push("hi")` is synthesized as `0x6869 << 240`
+            "61686960f01b6168696161005f5ff341420ac3a9"
+        );
+
+        let err = compile_source(
+            &format!(
+                "contract C {{
+                    block main {{ push(\"{}\") }}
+                }}",
+                "a".repeat(33)
+            ),
+            "C",
+        )
+        .unwrap_err();
+        assert!(err.contains("main.mep:2:39"), "{}", err);
+        assert!(err.contains("Push content exceeds 32 bytes."), "{}", err);
+    }
 }
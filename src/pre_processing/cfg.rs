@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
+
+use super::attribute::number_value;
+use crate::ast::{RAttribute, RAttributeArg, RBlockItem, RFile, WithAttributes};
+use crate::parser::error::{PestError, new_error_from_located};
+use crate::parser::parser::Located;
+use crate::parser::source::Sources;
+use crate::types::bytes32::Bytes32;
+
+const CFG_EXAMPLE: &str =
+    "ex: #[cfg($TESTNET$)], #[cfg(not($TESTNET$))] or #[cfg($CHAINID$ = 0x01)]";
+
+/// Removes the contracts, blocks, constants and block items excluded by their `cfg` attributes,
+/// and the `cfg` attributes of the others, before any analysis.
+pub fn strip_cfg(
+    input: &Sources,
+    r_file: &mut RFile,
+    compile_variables: &HashMap<String, Bytes>,
+) -> Result<(), PestError> {
+    let mut contracts = Vec::with_capacity(r_file.contracts.len());
+    for mut r_contract_with_attr in std::mem::take(&mut r_file.contracts) {
+        if !strip_attributes(
+            input,
+            &mut r_contract_with_attr.inner.attributes,
+            compile_variables,
+        )? {
+            continue;
+        }
+        let r_contract = &mut r_contract_with_attr.inner.inner.inner;
+
+        let mut blocks = Vec::with_capacity(r_contract.blocks.len());
+        for mut r_block_with_attr in std::mem::take(&mut r_contract.blocks) {
+            if !strip_attributes(
+                input,
+                &mut r_block_with_attr.inner.attributes,
+                compile_variables,
+            )? {
+                continue;
+            }
+            let r_block = &mut r_block_with_attr.inner.inner.inner;
+            strip_items(input, &mut r_block.items, compile_variables)?;
+            blocks.push(r_block_with_attr);
+        }
+        r_contract.blocks = blocks;
+
+        let mut constants = Vec::with_capacity(r_contract.constants.len());
+        for mut r_constant in std::mem::take(&mut r_contract.constants) {
+            if !strip_attributes(input, &mut r_constant.inner.attributes, compile_variables)? {
+                continue;
+            }
+            if let Some(r_attribute) = r_constant.attributes.first() {
+                return Err(new_error_from_located(
+                    input,
+                    r_attribute,
+                    "Only the attribute `cfg` applies to a constant.",
+                ));
+            }
+            constants.push(r_constant);
+        }
+        r_contract.constants = constants;
+
+        contracts.push(r_contract_with_attr);
+    }
+    r_file.contracts = contracts;
+
+    Ok(())
+}
+
+fn strip_items(
+    input: &Sources,
+    r_items: &mut Vec<Located<WithAttributes<Located<RBlockItem>>>>,
+    compile_variables: &HashMap<String, Bytes>,
+) -> Result<(), PestError> {
+    let mut items = Vec::with_capacity(r_items.len());
+    for mut r_item_with_attr in std::mem::take(r_items) {
+        if !strip_attributes(
+            input,
+            &mut r_item_with_attr.inner.attributes,
+            compile_variables,
+        )? {
+            continue;
+        }
+        if let RBlockItem::Repeat(r_repeat) = &mut r_item_with_attr.inner.inner.inner {
+            strip_items(input, &mut r_repeat.items, compile_variables)?;
+        }
+        items.push(r_item_with_attr);
+    }
+    *r_items = items;
+    Ok(())
+}
+
+/// Removes the `cfg` attributes, and returns whether all their conditions hold.
+fn strip_attributes(
+    input: &Sources,
+    attributes: &mut Vec<Located<RAttribute>>,
+    compile_variables: &HashMap<String, Bytes>,
+) -> Result<bool, PestError> {
+    let mut enabled = true;
+    for r_attribute in attributes.iter().filter(|attr| attr.name_str() == "cfg") {
+        let [arg] = r_attribute.args.as_slice() else {
+            return Err(new_error_from_located(
+                input,
+                r_attribute,
+                &format!(
+                    "The attribute `cfg` takes a single condition - {}",
+                    CFG_EXAMPLE
+                ),
+            ));
+        };
+        enabled &= condition_holds(input, arg, compile_variables)?;
+    }
+    attributes.retain(|attr| attr.name_str() != "cfg");
+    Ok(enabled)
+}
+
+/// A compile variable alone holds if it is set to a non-zero value. An equality never holds if the
+/// compile variable is not set.
+fn condition_holds(
+    input: &Sources,
+    arg: &Located<RAttributeArg>,
+    compile_variables: &HashMap<String, Bytes>,
+) -> Result<bool, PestError> {
+    match &arg.inner {
+        RAttributeArg::CompileVariable(compile_variable) => Ok(compile_variables
+            .get(compile_variable.as_str())
+            .is_some_and(|value| value.iter().any(|byte| *byte != 0))),
+        RAttributeArg::CompileVariableEquality(eq) => {
+            let expected = number_value(input, &eq.value, compile_variables)?;
+            let Some(value) = compile_variables.get(eq.name.as_str()) else {
+                return Ok(false);
+            };
+            Ok(Bytes32::from_bytes(value, true).is_some_and(|value| value == expected))
+        }
+        RAttributeArg::Not(inner) => Ok(!condition_holds(input, inner, compile_variables)?),
+        _ => Err(new_error_from_located(
+            input,
+            arg,
+            &format!(
+                "Expected a condition on a compile variable - {}",
+                CFG_EXAMPLE
+            ),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile::file::compile_sources;
+    use crate::compile::settings::CompilerSettings;
+
+    const SOURCE: &str = "
+        contract C {
+            #[cfg($TESTNET$)]
+            const X = 0x11;
+            #[cfg(not($TESTNET$))]
+            const X = 0x22;
+
+            block main {
+                push(X)
+                #[cfg($CHAINID$ = 0x01)]
+                caller
+                #[cfg(not($CHAINID$ = 0x01))]
+                origin
+            }
+        }
+    ";
+
+    fn compile_with(variables: &[(&str, u8)]) -> Result<String, String> {
+        let settings = CompilerSettings {
+            variables: variables
+                .iter()
+                .map(|(name, value)| (name.to_string(), Bytes::from(vec![*value])))
+                .collect(),
+            ..Default::default()
+        };
+        let artifacts = compile_sources(&[("main.mep", SOURCE)], "C", settings)?;
+        Ok(hex::encode(artifacts.main_bytecode()))
+    }
+
+    #[test]
+    fn test_conditions() {
+        assert_eq!(
+            compile_with(&[("TESTNET", 0x01), ("CHAINID", 0x01)]).unwrap(),
+            "601133"
+        );
+        assert_eq!(
+            compile_with(&[("TESTNET", 0x00), ("CHAINID", 0x0a)]).unwrap(),
+            "602232"
+        );
+        assert_eq!(compile_with(&[]).unwrap(), "602232");
+    }
+
+    #[test]
+    fn test_invalid_condition() {
+        let err = compile_sources(
+            &[(
+                "main.mep",
+                "contract C {
+                    #[cfg(testnet)]
+                    block main { stop }
+                }",
+            )],
+            "C",
+            CompilerSettings::default(),
+        )
+        .unwrap_err();
+        assert!(err.contains("main.mep:2:27"), "{}", err);
+        assert!(
+            err.contains("Expected a condition on a compile variable"),
+            "{}",
+            err
+        );
+    }
+}
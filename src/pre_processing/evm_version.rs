@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+
+use super::opcode::*;
+
+/// The hardforks of Ethereum that changed the set of opcodes, in chronological order.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EvmVersion {
+    Homestead,
+    Byzantium,
+    Constantinople,
+    Petersburg,
+    Istanbul,
+    Berlin,
+    London,
+    Paris,
+    Shanghai,
+    Cancun,
+    Prague,
+    #[default]
+    Osaka,
+}
+
+impl EvmVersion {
+    pub const ALL: [Self; 12] = [
+        Self::Homestead,
+        Self::Byzantium,
+        Self::Constantinople,
+        Self::Petersburg,
+        Self::Istanbul,
+        Self::Berlin,
+        Self::London,
+        Self::Paris,
+        Self::Shanghai,
+        Self::Cancun,
+        Self::Prague,
+        Self::Osaka,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Homestead => "homestead",
+            Self::Byzantium => "byzantium",
+            Self::Constantinople => "constantinople",
+            Self::Petersburg => "petersburg",
+            Self::Istanbul => "istanbul",
+            Self::Berlin => "berlin",
+            Self::London => "london",
+            Self::Paris => "paris",
+            Self::Shanghai => "shanghai",
+            Self::Cancun => "cancun",
+            Self::Prague => "prague",
+            Self::Osaka => "osaka",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|version| version.as_str() == name)
+    }
+
+    pub fn has_push0(self) -> bool {
+        self >= Self::Shanghai
+    }
+
+    pub fn has_shifts(self) -> bool {
+        self >= Self::Constantinople
+    }
+}
+
+/// The first EVM version in which the opcode is defined.
+pub fn required_evm_version(op: OpCode) -> EvmVersion {
+    match op {
+        RETURNDATASIZE | RETURNDATACOPY | STATICCALL | REVERT => EvmVersion::Byzantium,
+        SHL | SHR | SAR | CREATE2 | EXTCODEHASH => EvmVersion::Constantinople,
+        CHAINID | SELFBALANCE => EvmVersion::Istanbul,
+        BASEFEE => EvmVersion::London,
+        PUSH0 => EvmVersion::Shanghai,
+        TLOAD | TSTORE | MCOPY | BLOBHASH | BLOBBASEFEE => EvmVersion::Cancun,
+        CLZ => EvmVersion::Osaka,
+        _ => EvmVersion::Homestead,
+    }
+}
+
+/// Error message if the opcode named `name` is not defined in the EVM version.
+pub fn check_evm_version(op: OpCode, name: &str, evm_version: EvmVersion) -> Result<(), String> {
+    let required = required_evm_version(op);
+    if evm_version < required {
+        Err(format!(
+            "`{}` requires {}, but the EVM version is {}.",
+            name.to_lowercase(),
+            required.as_str(),
+            evm_version.as_str()
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile::file::{compile_source, compile_sources};
+    use crate::compile::settings::CompilerSettings;
+
+    fn compile_for(source: &str, evm_version: EvmVersion) -> Result<String, String> {
+        let settings = CompilerSettings {
+            evm_version,
+            ..Default::default()
+        };
+        let artifacts = compile_sources(&[("main.mep", source)], "C", settings)?;
+        Ok(hex::encode(artifacts.main_bytecode()))
+    }
+
+    #[test]
+    fn test_opcode_gating() {
+        let source = "contract C {
+            block main { push(0x) push(0x) tstore }
+        }";
+        assert_eq!(compile_for(source, EvmVersion::Cancun).unwrap(), "5f5f5d");
+        let err = compile_for(source, EvmVersion::London).unwrap_err();
+        assert!(err.contains("main.mep:2:44"), "{}", err);
+        assert!(
+            err.contains("`tstore` requires cancun, but the EVM version is london."),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_synthesis_gating() {
+        let source = format!(
+            "contract C {{
+                block main {{ caller push(0x) push(0x{}) push({}) }}
+            }}",
+            "ff".repeat(32),
+            "1 << 255"
+        );
+        assert_eq!(
+            compile_for(&source, EvmVersion::Osaka).unwrap(),
+            "335f5f19600160ff1b"
+        );
+        assert_eq!(
+            compile_for(&source, EvmVersion::London).unwrap(),
+            "336000600019600160ff1b"
+        );
+        assert_eq!(
+            compile_for(&source, EvmVersion::Byzantium).unwrap(),
+            format!("3360006000197f80{}", "00".repeat(31))
+        );
+
+        // the attribute of the contract overrides the settings
+        let artifacts = compile_source(
+            "#[evm_version(\"london\")]
+            contract C {
+                block main { caller push(0x) }
+            }",
+            "C",
+        )
+        .unwrap();
+        assert_eq!(hex::encode(artifacts.main_bytecode()), "336000");
+    }
+}
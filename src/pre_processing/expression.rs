@@ -0,0 +1,549 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+use bytes::{Bytes, BytesMut};
+
+use super::pre_processing::get_compile_variable_value;
+use super::signature::{canonicalize_signature, keccak256};
+use crate::ast::{
+    RBinaryOperator, RExpression, RExpressionCall, RUnaryOperator, RVariableWithField,
+};
+use crate::parser::error::{PestError, new_error_from_located, new_error_from_location};
+use crate::parser::parser::{Located, Location};
+use crate::parser::source::Sources;
+use crate::types::bytes32::Bytes32;
+
+/// Value of a constant expression. Literals, constants and concatenations keep their bytes as
+/// written, arithmetic results use the minimal number of bytes, and negative results are encoded
+/// on 32 bytes in two's complement.
+#[derive(Clone, Debug)]
+pub struct Value {
+    pub bytes: Bytes,
+    pub negative: bool,
+}
+
+impl Value {
+    /// Unsigned value using the minimal number of bytes.
+    pub fn from_word(word: Bytes32) -> Self {
+        Number::new(false, word).to_value().unwrap()
+    }
+}
+
+impl From<Bytes> for Value {
+    fn from(bytes: Bytes) -> Self {
+        Self {
+            bytes,
+            negative: false,
+        }
+    }
+}
+
+/// Signed 257 bits integer on which the arithmetic operators are applied.
+#[derive(Clone, Debug)]
+struct Number {
+    negative: bool,
+    magnitude: Bytes32,
+}
+
+impl Number {
+    fn new(negative: bool, magnitude: Bytes32) -> Self {
+        Self {
+            negative: negative && !magnitude.is_zero(),
+            magnitude,
+        }
+    }
+
+    fn from_value(value: &Value) -> Option<Self> {
+        let word = Bytes32::from_bytes(&value.bytes, true)?;
+        Some(if value.negative {
+            Self::new(true, negate(&word))
+        } else {
+            Self::new(false, word)
+        })
+    }
+
+    /// Two's complement representation on 256 bits.
+    fn to_word(&self) -> Bytes32 {
+        if self.negative {
+            negate(&self.magnitude)
+        } else {
+            self.magnitude.clone()
+        }
+    }
+
+    /// Builds a number from its two's complement representation, negative if `negative` is set.
+    fn from_word(word: Bytes32, negative: bool) -> Self {
+        if negative {
+            Self::new(true, negate(&word))
+        } else {
+            Self::new(false, word)
+        }
+    }
+
+    fn to_value(&self) -> Option<Value> {
+        if self.negative {
+            // -2^255 is the lowest value that fits in 256 bits.
+            if self.magnitude > Bytes32::from_usize(1).shl(255) {
+                return None;
+            }
+            return Some(Value {
+                bytes: Bytes::copy_from_slice(&self.to_word()[..]),
+                negative: true,
+            });
+        }
+
+        let content = self.magnitude.right_content();
+        Some(if content.is_empty() {
+            Bytes::from_static(&[0]).into()
+        } else {
+            Bytes::copy_from_slice(content).into()
+        })
+    }
+
+    fn add(&self, other: &Self) -> Option<Self> {
+        if self.negative == other.negative {
+            return Some(Self::new(
+                self.negative,
+                self.magnitude.checked_add(&other.magnitude)?,
+            ));
+        }
+        Some(if self.magnitude >= other.magnitude {
+            Self::new(
+                self.negative,
+                self.magnitude.overflowing_sub(&other.magnitude).0,
+            )
+        } else {
+            Self::new(
+                other.negative,
+                other.magnitude.overflowing_sub(&self.magnitude).0,
+            )
+        })
+    }
+
+    fn neg(&self) -> Self {
+        Self::new(!self.negative, self.magnitude.clone())
+    }
+}
+
+fn negate(word: &Bytes32) -> Bytes32 {
+    (!word).overflowing_add(&Bytes32::from_usize(1)).0
+}
+
+/// Expression whose leaves are only known later in the compilation, e.g. block offsets that are
+/// resolved once the contract is laid out. Sub-expressions that do not depend on any leaf are
+/// evaluated as soon as possible.
+#[derive(Clone, Debug)]
+pub enum Expression<L> {
+    Value(Value),
+    Leaf(L),
+    Unary {
+        op: RUnaryOperator,
+        operand: Box<Located<Expression<L>>>,
+    },
+    Binary {
+        op: RBinaryOperator,
+        lhs: Box<Located<Expression<L>>>,
+        rhs: Box<Located<Expression<L>>>,
+    },
+}
+
+impl<L> Located<Expression<L>> {
+    pub fn map_leaves<M>(self, f: &mut dyn FnMut(L) -> Expression<M>) -> Located<Expression<M>> {
+        Located {
+            location: self.location,
+            inner: match self.inner {
+                Expression::Value(value) => Expression::Value(value),
+                Expression::Leaf(leaf) => f(leaf),
+                Expression::Unary { op, operand } => Expression::Unary {
+                    op,
+                    operand: Box::new(operand.map_leaves(f)),
+                },
+                Expression::Binary { op, lhs, rhs } => Expression::Binary {
+                    op,
+                    lhs: Box::new(lhs.map_leaves(f)),
+                    rhs: Box::new(rhs.map_leaves(f)),
+                },
+            },
+        }
+    }
+
+    pub fn for_each_leaf(&self, f: &mut dyn FnMut(&L)) {
+        match &self.inner {
+            Expression::Value(_) => (),
+            Expression::Leaf(leaf) => f(leaf),
+            Expression::Unary { op: _, operand } => operand.for_each_leaf(f),
+            Expression::Binary { op: _, lhs, rhs } => {
+                lhs.for_each_leaf(f);
+                rhs.for_each_leaf(f);
+            }
+        }
+    }
+
+    pub fn for_each_leaf_mut(&mut self, f: &mut dyn FnMut(&mut L)) {
+        match &mut self.inner {
+            Expression::Value(_) => (),
+            Expression::Leaf(leaf) => f(leaf),
+            Expression::Unary { op: _, operand } => operand.for_each_leaf_mut(f),
+            Expression::Binary { op: _, lhs, rhs } => {
+                lhs.for_each_leaf_mut(f);
+                rhs.for_each_leaf_mut(f);
+            }
+        }
+    }
+
+    /// Evaluates the sub-expressions that do not depend on any leaf anymore.
+    pub fn fold(self, input: &Sources) -> Result<Self, PestError> {
+        let inner = match self.inner {
+            Expression::Unary { op, operand } => {
+                combine_unary(input, &self.location, op, operand.fold(input)?)?
+            }
+            Expression::Binary { op, lhs, rhs } => combine_binary(
+                input,
+                &self.location,
+                op,
+                lhs.fold(input)?,
+                rhs.fold(input)?,
+            )?,
+            inner => inner,
+        };
+        Ok(Located {
+            location: self.location,
+            inner,
+        })
+    }
+
+    /// Evaluates the expression, the leaves being resolved to unsigned values by `resolve_leaf`.
+    pub fn evaluate(
+        self,
+        input: &Sources,
+        resolve_leaf: &mut dyn FnMut(&L) -> Bytes32,
+    ) -> Result<Value, PestError> {
+        let folded = self
+            .map_leaves::<L>(&mut |leaf| Expression::Value(Value::from_word(resolve_leaf(&leaf))))
+            .fold(input)?;
+        let Expression::Value(value) = folded.inner else {
+            unreachable!()
+        };
+        Ok(value)
+    }
+}
+
+/// Builds an expression, variables and fields being resolved by `resolve_variable` and
+/// `resolve_field`.
+#[allow(clippy::type_complexity)]
+pub fn build_expression<L>(
+    input: &Sources,
+    expression: &Located<RExpression>,
+    resolve_variable: &mut dyn FnMut(&str, &Location) -> Result<Expression<L>, PestError>,
+    resolve_field: &mut dyn FnMut(&RVariableWithField) -> Result<Expression<L>, PestError>,
+    compile_variables: &HashMap<String, Bytes>,
+) -> Result<Located<Expression<L>>, PestError> {
+    let inner = match &expression.inner {
+        RExpression::HexLiteral(hex_literal) => Expression::Value(hex_literal.0.clone().into()),
+        RExpression::StringLiteral(string_literal) => {
+            Expression::Value(Bytes::copy_from_slice(string_literal.as_str().as_bytes()).into())
+        }
+        RExpression::DecimalLiteral(decimal_literal) => {
+            Expression::Value(Value::from_word(decimal_literal.0.clone()))
+        }
+        RExpression::CompileVariable(compile_variable) => Expression::Value(
+            get_compile_variable_value(input, compile_variable, compile_variables)?
+                .clone()
+                .into(),
+        ),
+        RExpression::Variable(variable) => {
+            resolve_variable(variable.as_str(), &expression.location)?
+        }
+        RExpression::VariableWithField(variable_with_field) => resolve_field(variable_with_field)?,
+        RExpression::Call(call) => Expression::Value(evaluate_call(input, call)?),
+        RExpression::Unary { op, operand } => {
+            let operand = build_expression(
+                input,
+                operand,
+                resolve_variable,
+                resolve_field,
+                compile_variables,
+            )?;
+            combine_unary(input, &expression.location, *op, operand)?
+        }
+        RExpression::Binary { op, lhs, rhs } => {
+            let lhs = build_expression(
+                input,
+                lhs,
+                resolve_variable,
+                resolve_field,
+                compile_variables,
+            )?;
+            let rhs = build_expression(
+                input,
+                rhs,
+                resolve_variable,
+                resolve_field,
+                compile_variables,
+            )?;
+            combine_binary(input, &expression.location, *op, lhs, rhs)?
+        }
+    };
+
+    Ok(Located {
+        location: expression.location.clone(),
+        inner,
+    })
+}
+
+/// Evaluates a constant expression. Variables are resolved by `resolve_variable`, that receives
+/// their name and location.
+pub fn evaluate_expression(
+    input: &Sources,
+    expression: &Located<RExpression>,
+    resolve_variable: &mut dyn FnMut(&str, &Location) -> Result<Value, PestError>,
+    compile_variables: &HashMap<String, Bytes>,
+) -> Result<Value, PestError> {
+    let expression = build_expression::<Infallible>(
+        input,
+        expression,
+        &mut |name, location| Ok(Expression::Value(resolve_variable(name, location)?)),
+        &mut |variable_with_field| {
+            Err(new_error_from_located(
+                input,
+                &variable_with_field.field,
+                &format!(
+                    "Field `{}` cannot be used in a constant expression.",
+                    variable_with_field.field.as_str()
+                ),
+            ))
+        },
+        compile_variables,
+    )?;
+    let Expression::Value(value) = expression.inner else {
+        unreachable!()
+    };
+    Ok(value)
+}
+
+/// Evaluates the hashing functions `selector`, `topic` and `keccak`.
+fn evaluate_call(input: &Sources, call: &RExpressionCall) -> Result<Value, PestError> {
+    let function_name = call.name.as_str();
+    let arg = call.arg.as_str();
+
+    let hash = match function_name {
+        "keccak" => keccak256(arg.as_bytes()),
+        "selector" | "topic" => {
+            let signature = canonicalize_signature(arg).map_err(|err| {
+                new_error_from_located(input, &call.arg, &format!("Invalid signature: {}.", err))
+            })?;
+            keccak256(signature.as_bytes())
+        }
+        _ => {
+            return Err(new_error_from_located(
+                input,
+                &call.name,
+                &format!("Unknown function `{}`.", function_name),
+            ));
+        }
+    };
+
+    let length = if function_name == "selector" { 4 } else { 32 };
+    Ok(Bytes::copy_from_slice(&hash[..length]).into())
+}
+
+fn combine_unary<L>(
+    input: &Sources,
+    location: &Location,
+    op: RUnaryOperator,
+    operand: Located<Expression<L>>,
+) -> Result<Expression<L>, PestError> {
+    let Expression::Value(value) = &operand.inner else {
+        return Ok(Expression::Unary {
+            op,
+            operand: Box::new(operand),
+        });
+    };
+
+    let number = to_number(input, &operand.location, value)?;
+    let res = match op {
+        RUnaryOperator::Neg => number.neg(),
+        RUnaryOperator::BitNot => Number::from_word(!&number.to_word(), !number.negative),
+    };
+    Ok(Expression::Value(to_value(input, location, &res)?))
+}
+
+fn combine_binary<L>(
+    input: &Sources,
+    location: &Location,
+    op: RBinaryOperator,
+    lhs: Located<Expression<L>>,
+    rhs: Located<Expression<L>>,
+) -> Result<Expression<L>, PestError> {
+    let (Expression::Value(lhs_value), Expression::Value(rhs_value)) = (&lhs.inner, &rhs.inner)
+    else {
+        if op == RBinaryOperator::Concat {
+            let operand = if matches!(lhs.inner, Expression::Value(_)) {
+                &rhs
+            } else {
+                &lhs
+            };
+            return Err(new_error_from_located(
+                input,
+                operand,
+                "Only constants can be concatenated.",
+            ));
+        }
+        return Ok(Expression::Binary {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        });
+    };
+
+    if op == RBinaryOperator::Concat {
+        let mut bytes = BytesMut::from(&lhs_value.bytes[..]);
+        bytes.extend_from_slice(&rhs_value.bytes);
+        return Ok(Expression::Value(Bytes::from(bytes).into()));
+    }
+
+    let a = to_number(input, &lhs.location, lhs_value)?;
+    let b = to_number(input, &rhs.location, rhs_value)?;
+    let res = apply_binary(input, location, op, a, b)?;
+    Ok(Expression::Value(to_value(input, location, &res)?))
+}
+
+fn apply_binary(
+    input: &Sources,
+    location: &Location,
+    op: RBinaryOperator,
+    a: Number,
+    b: Number,
+) -> Result<Number, PestError> {
+    let overflow = || overflow_error(input, location);
+    Ok(match op {
+        RBinaryOperator::Add => a.add(&b).ok_or_else(overflow)?,
+        RBinaryOperator::Sub => a.add(&b.neg()).ok_or_else(overflow)?,
+        RBinaryOperator::Mul => Number::new(
+            a.negative != b.negative,
+            a.magnitude.checked_mul(&b.magnitude).ok_or_else(overflow)?,
+        ),
+        RBinaryOperator::Div | RBinaryOperator::Rem => {
+            let Some((quotient, remainder)) = a.magnitude.div_rem(&b.magnitude) else {
+                return Err(new_error_from_location(
+                    input,
+                    location,
+                    "Division by zero.",
+                ));
+            };
+            if op == RBinaryOperator::Div {
+                Number::new(a.negative != b.negative, quotient)
+            } else {
+                Number::new(a.negative, remainder)
+            }
+        }
+        RBinaryOperator::Shl | RBinaryOperator::Shr => {
+            let shift = if b.negative {
+                None
+            } else {
+                b.magnitude.to_usize()
+            };
+            let Some(shift) = shift else {
+                return Err(new_error_from_location(
+                    input,
+                    location,
+                    "Invalid shift amount.",
+                ));
+            };
+            if op == RBinaryOperator::Shl {
+                Number::new(
+                    a.negative,
+                    a.magnitude.checked_shl(shift).ok_or_else(overflow)?,
+                )
+            } else if a.negative {
+                // Arithmetic shift, rounding towards negative infinity.
+                let magnitude = a.magnitude.overflowing_sub(&Bytes32::from_usize(1)).0;
+                Number::new(
+                    true,
+                    magnitude
+                        .shr(shift)
+                        .overflowing_add(&Bytes32::from_usize(1))
+                        .0,
+                )
+            } else {
+                Number::new(false, a.magnitude.shr(shift))
+            }
+        }
+        RBinaryOperator::BitAnd => {
+            Number::from_word(&a.to_word() & &b.to_word(), a.negative && b.negative)
+        }
+        RBinaryOperator::BitOr => {
+            Number::from_word(&a.to_word() | &b.to_word(), a.negative || b.negative)
+        }
+        RBinaryOperator::BitXor => {
+            Number::from_word(&a.to_word() ^ &b.to_word(), a.negative != b.negative)
+        }
+        RBinaryOperator::Concat => unreachable!(),
+    })
+}
+
+fn to_number(input: &Sources, location: &Location, value: &Value) -> Result<Number, PestError> {
+    Number::from_value(value)
+        .ok_or_else(|| new_error_from_location(input, location, "Operand exceeds 256 bits."))
+}
+
+fn to_value(input: &Sources, location: &Location, number: &Number) -> Result<Value, PestError> {
+    number
+        .to_value()
+        .ok_or_else(|| overflow_error(input, location))
+}
+
+fn overflow_error(input: &Sources, location: &Location) -> PestError {
+    new_error_from_location(input, location, "Overflow: the result exceeds 256 bits.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile::file::compile_source;
+
+    #[test]
+    fn test_two_complement() {
+        let one = Bytes32::from_usize(1);
+        let min = one.shl(255);
+
+        assert_eq!(negate(&one), !&Bytes32::default());
+        assert_eq!(
+            Number::new(true, one.clone()).to_word(),
+            !&Bytes32::default()
+        );
+        assert_eq!(negate(&min), min);
+
+        let value = Number::new(true, min.clone()).to_value().unwrap();
+        assert!(value.negative);
+        assert_eq!(value.bytes.len(), 32);
+        assert_eq!(value.bytes[0], 0x80);
+        assert!(value.bytes[1..].iter().all(|byte| *byte == 0));
+
+        let number = Number::from_value(&value).unwrap();
+        assert!(number.negative);
+        assert_eq!(number.magnitude, min);
+
+        let below_min = min.overflowing_add(&one).0;
+        assert!(Number::new(true, below_min).to_value().is_none());
+        assert!(Number::new(false, min).to_value().is_some());
+    }
+
+    #[test]
+    fn test_overflow_error() {
+        let err = compile_source(
+            "contract C {
+                const X = (1 << 255) * 2;
+                block main { push(X) }
+            }",
+            "C",
+        )
+        .unwrap_err();
+        assert!(err.contains("main.mep:2:27"), "{}", err);
+        assert!(
+            err.contains("Overflow: the result exceeds 256 bits."),
+            "{}",
+            err
+        );
+    }
+}
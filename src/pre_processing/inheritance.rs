@@ -0,0 +1,348 @@
+use std::collections::{HashMap, HashSet};
+
+use indexmap::IndexMap;
+
+use crate::ast::{RBlock, RConstant, RContract, RImmutable, RLink, WithAttributes};
+use crate::parser::error::{PestError, new_error_from_located};
+use crate::parser::parser::Located;
+use crate::parser::source::Sources;
+
+type RContractWithAttr = Located<WithAttributes<Located<RContract>>>;
+
+#[derive(Clone, Debug)]
+struct FlattenedContract {
+    contract: RContractWithAttr,
+    /// Index of the contract declaring each block.
+    block_origins: HashMap<String, usize>,
+    /// Index of the contract declaring each constant.
+    constant_origins: HashMap<String, usize>,
+}
+
+/// Flattens the inheritance of the contracts. The constants, immutables, links and blocks of the
+/// bases are merged into the derived contracts, that can override them by name. The attributes of the bases are
+/// applied before the ones of the derived contract.
+pub struct ContractFlattener<'a> {
+    input: &'a Sources,
+    r_contracts: &'a [RContractWithAttr],
+    contract_names: &'a HashMap<String, usize>,
+    flattened: HashMap<usize, FlattenedContract>,
+    visiting: HashSet<usize>,
+    bases: HashSet<usize>,
+}
+
+impl<'a> ContractFlattener<'a> {
+    pub fn new(
+        input: &'a Sources,
+        r_contracts: &'a [RContractWithAttr],
+        contract_names: &'a HashMap<String, usize>,
+    ) -> Self {
+        Self {
+            input,
+            r_contracts,
+            contract_names,
+            flattened: HashMap::new(),
+            visiting: HashSet::new(),
+            bases: HashSet::new(),
+        }
+    }
+
+    pub fn flatten(&mut self, contract_index: usize) -> Result<&RContractWithAttr, PestError> {
+        self.flatten_if_needed(contract_index)?;
+        Ok(&self.flattened.get(&contract_index).unwrap().contract)
+    }
+
+    /// Whether the contract has been used as a base by one of the contracts flattened so far.
+    pub fn is_base(&self, contract_index: usize) -> bool {
+        self.bases.contains(&contract_index)
+    }
+
+    fn flatten_if_needed(&mut self, contract_index: usize) -> Result<(), PestError> {
+        if self.flattened.contains_key(&contract_index) {
+            return Ok(());
+        }
+
+        let r_contract_with_attr = &self.r_contracts[contract_index];
+        let r_contract = r_contract_with_attr.inner();
+
+        if r_contract.bases.is_empty() {
+            self.flattened.insert(
+                contract_index,
+                FlattenedContract {
+                    contract: r_contract_with_attr.clone(),
+                    block_origins: r_contract
+                        .blocks
+                        .iter()
+                        .map(|r_block| (r_block.name_str().to_owned(), contract_index))
+                        .collect(),
+                    constant_origins: r_contract
+                        .constants
+                        .iter()
+                        .map(|r_constant| (r_constant.name_str().to_owned(), contract_index))
+                        .collect(),
+                },
+            );
+            return Ok(());
+        }
+
+        self.visiting.insert(contract_index);
+
+        let mut attributes = Vec::new();
+        let mut blocks =
+            IndexMap::<String, (Located<WithAttributes<Located<RBlock>>>, usize)>::new();
+        let mut constants = IndexMap::<String, (Located<RConstant>, usize)>::new();
+        let mut immutables = IndexMap::<String, Located<RImmutable>>::new();
+        let mut links = IndexMap::<String, Located<RLink>>::new();
+
+        let own_names = r_contract
+            .blocks
+            .iter()
+            .map(|r_block| r_block.name_str())
+            .chain(
+                r_contract
+                    .constants
+                    .iter()
+                    .map(|r_constant| r_constant.name_str()),
+            )
+            .collect::<HashSet<_>>();
+
+        for base in &r_contract.bases {
+            let base_name = base.as_str();
+            let Some(base_index) = self.contract_names.get(base_name).copied() else {
+                return Err(new_error_from_located(
+                    self.input,
+                    base,
+                    &format!("Contract `{}` not found.", base_name),
+                ));
+            };
+
+            if self.visiting.contains(&base_index) {
+                return Err(new_error_from_located(
+                    self.input,
+                    base,
+                    "Recursive inheritance unhandled.",
+                ));
+            }
+
+            self.flatten_if_needed(base_index)?;
+            self.bases.insert(base_index);
+
+            let flattened_base = self.flattened.get(&base_index).unwrap();
+            let r_base = flattened_base.contract.inner();
+            attributes.extend(flattened_base.contract.attributes.iter().cloned());
+
+            for r_block in &r_base.blocks {
+                let block_name = r_block.name_str();
+                let origin = *flattened_base.block_origins.get(block_name).unwrap();
+                if let Some((_, previous_origin)) = blocks.get(block_name) {
+                    if *previous_origin != origin && !own_names.contains(block_name) {
+                        return Err(self.conflict_error(r_contract, "Block", block_name));
+                    }
+                    continue;
+                }
+                if constants.contains_key(block_name) && !own_names.contains(block_name) {
+                    return Err(self.conflict_error(r_contract, "Name", block_name));
+                }
+                blocks.insert(block_name.to_owned(), (r_block.clone(), origin));
+            }
+
+            for r_constant in &r_base.constants {
+                let constant_name = r_constant.name_str();
+                let origin = *flattened_base.constant_origins.get(constant_name).unwrap();
+                if let Some((_, previous_origin)) = constants.get(constant_name) {
+                    if *previous_origin != origin && !own_names.contains(constant_name) {
+                        return Err(self.conflict_error(r_contract, "Constant", constant_name));
+                    }
+                    continue;
+                }
+                if blocks.contains_key(constant_name) && !own_names.contains(constant_name) {
+                    return Err(self.conflict_error(r_contract, "Name", constant_name));
+                }
+                constants.insert(constant_name.to_owned(), (r_constant.clone(), origin));
+            }
+
+            for r_immutable in &r_base.immutables {
+                immutables
+                    .entry(r_immutable.name_str().to_owned())
+                    .or_insert_with(|| r_immutable.clone());
+            }
+
+            for r_link in &r_base.links {
+                links
+                    .entry(r_link.name_str().to_owned())
+                    .or_insert_with(|| r_link.clone());
+            }
+        }
+
+        for r_block in &r_contract.blocks {
+            let block_name = r_block.name_str();
+            if constants.contains_key(block_name) {
+                return Err(new_error_from_located(
+                    self.input,
+                    &r_block.inner().name,
+                    &format!(
+                        "Block `{}` cannot override an inherited constant.",
+                        block_name
+                    ),
+                ));
+            }
+            if let Some((overridden, origin)) = blocks.get_mut(block_name) {
+                if overridden.inner().abstr != r_block.inner().abstr {
+                    return Err(new_error_from_located(
+                        self.input,
+                        &r_block.inner().name,
+                        &format!(
+                            "Block `{}` must be {}abstract to override the block of `{}`.",
+                            block_name,
+                            if overridden.inner().abstr { "" } else { "non-" },
+                            self.r_contracts[*origin].inner().name_str(),
+                        ),
+                    ));
+                }
+                if overridden.inner().data != r_block.inner().data {
+                    return Err(new_error_from_located(
+                        self.input,
+                        &r_block.inner().name,
+                        &format!(
+                            "Block `{}` must {}be a data block to override the block of `{}`.",
+                            block_name,
+                            if overridden.inner().data { "" } else { "not " },
+                            self.r_contracts[*origin].inner().name_str(),
+                        ),
+                    ));
+                }
+                *overridden = r_block.clone();
+                *origin = contract_index;
+            } else {
+                blocks.insert(block_name.to_owned(), (r_block.clone(), contract_index));
+            }
+        }
+
+        for r_constant in &r_contract.constants {
+            let constant_name = r_constant.name_str();
+            if blocks.contains_key(constant_name) {
+                return Err(new_error_from_located(
+                    self.input,
+                    &r_constant.name,
+                    &format!(
+                        "Constant `{}` cannot override an inherited block.",
+                        constant_name
+                    ),
+                ));
+            }
+            constants.insert(
+                constant_name.to_owned(),
+                (r_constant.clone(), contract_index),
+            );
+        }
+
+        for r_immutable in &r_contract.immutables {
+            immutables.insert(r_immutable.name_str().to_owned(), r_immutable.clone());
+        }
+
+        for r_link in &r_contract.links {
+            links.insert(r_link.name_str().to_owned(), r_link.clone());
+        }
+
+        attributes.extend(r_contract_with_attr.attributes.iter().cloned());
+
+        self.visiting.remove(&contract_index);
+        self.flattened.insert(
+            contract_index,
+            FlattenedContract {
+                contract: Located {
+                    location: r_contract_with_attr.location.clone(),
+                    inner: WithAttributes {
+                        attributes,
+                        inner: Located {
+                            location: r_contract.location.clone(),
+                            inner: RContract {
+                                name: r_contract.name.clone(),
+                                bases: r_contract.bases.clone(),
+                                blocks: blocks
+                                    .values()
+                                    .map(|(r_block, _)| r_block.clone())
+                                    .collect(),
+                                constants: constants
+                                    .values()
+                                    .map(|(r_constant, _)| r_constant.clone())
+                                    .collect(),
+                                immutables: immutables.into_values().collect(),
+                                links: links.into_values().collect(),
+                            },
+                        },
+                    },
+                },
+                block_origins: blocks
+                    .iter()
+                    .map(|(name, (_, origin))| (name.clone(), *origin))
+                    .collect(),
+                constant_origins: constants
+                    .iter()
+                    .map(|(name, (_, origin))| (name.clone(), *origin))
+                    .collect(),
+            },
+        );
+
+        Ok(())
+    }
+
+    fn conflict_error(&self, r_contract: &Located<RContract>, kind: &str, name: &str) -> PestError {
+        new_error_from_located(
+            self.input,
+            &r_contract.name,
+            &format!(
+                "{} `{}` is inherited from multiple bases, it must be overridden.",
+                kind, name
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compile::file::compile_source;
+
+    const BASE: &str = "
+        #[assume(msize = 0x20)]
+        contract Base {
+            const X = 0x01;
+            block main { push(X) push(0x20) &end }
+            abstract block end { revert }
+        }
+    ";
+
+    #[test]
+    fn test_override() {
+        let source = format!(
+            "{}
+            contract Derived is Base {{
+                const X = 0x02;
+                abstract block end {{ return }}
+            }}",
+            BASE
+        );
+        let artifacts = compile_source(&source, "Base").unwrap();
+        assert_eq!(hex::encode(artifacts.main_bytecode()), "600159fd");
+        let artifacts = compile_source(&source, "Derived").unwrap();
+        assert_eq!(hex::encode(artifacts.main_bytecode()), "600259f3");
+    }
+
+    #[test]
+    fn test_conflict() {
+        let source = format!(
+            "{}
+            contract Other {{
+                const X = 0x02;
+            }}
+            contract Derived is Base, Other {{}}",
+            BASE
+        );
+        let err = compile_source(&source, "Derived").unwrap_err();
+        assert!(err.contains("main.mep:12:22"), "{}", err);
+        assert!(
+            err.contains("Constant `X` is inherited from multiple bases, it must be overridden."),
+            "{}",
+            err
+        );
+    }
+}
@@ -1,8 +1,14 @@
 pub mod attribute;
+pub mod evm_version;
+pub mod expression;
 pub mod opcode;
 pub mod pre_processing;
 
 mod block_flow;
+mod cfg;
 mod dependencies;
+mod inheritance;
 mod queue;
 mod remapping;
+mod signature;
+mod stack;
@@ -1,7 +1,22 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
 pub type OpCode = u8;
 
 // pub const PUSH_GAS: usize = 3;
 
+// 0x0 range - arithmetic ops.
+pub const ADD: OpCode = 0x01;
+pub const SUB: OpCode = 0x03;
+
+// 0x10 range - comparison ops.
+pub const NOT: OpCode = 0x19;
+pub const SHL: OpCode = 0x1b;
+pub const SHR: OpCode = 0x1c;
+pub const SAR: OpCode = 0x1d;
+pub const CLZ: OpCode = 0x1e;
+
 // 0x30 range - closure state.
 pub const ADDRESS: OpCode = 0x30;
 pub const BALANCE: OpCode = 0x31;
@@ -54,16 +69,30 @@ pub const MCOPY: OpCode = 0x5e;
 
 pub const PUSH0: OpCode = 0x5f;
 pub const PUSH1: OpCode = 0x60;
-pub const PUSH2: OpCode = 0x61;
 pub const PUSH32: OpCode = 0x7f;
 
+// 0x80 range - dups.
+pub const DUP1: OpCode = 0x80;
+pub const DUP16: OpCode = 0x8f;
+
+// 0x90 range - swaps.
+pub const SWAP1: OpCode = 0x90;
+pub const SWAP16: OpCode = 0x9f;
+
+// 0xf0 range - closures.
+pub const RETURN: OpCode = 0xf3;
+pub const CREATE2: OpCode = 0xf5;
+pub const STATICCALL: OpCode = 0xfa;
+pub const REVERT: OpCode = 0xfd;
+pub const INVALID: OpCode = 0xfe;
+
 pub fn str_to_op(name: &str) -> Option<OpCode> {
     Some(match name.to_lowercase().as_str() {
         // 0x0 range - arithmetic ops.
         "stop" => 0x00,
-        "add" => 0x01,
+        "add" => ADD,
         "mul" => 0x02,
-        "sub" => 0x03,
+        "sub" => SUB,
         "div" => 0x04,
         "sdiv" => 0x05,
         "mod" => 0x06,
@@ -83,11 +112,12 @@ pub fn str_to_op(name: &str) -> Option<OpCode> {
         "and" => 0x16,
         "or" => 0x17,
         "xor" => 0x18,
-        "not" => 0x19,
+        "not" => NOT,
         "byte" => 0x1a,
-        "shl" => 0x1b,
-        "shr" => 0x1c,
-        "sar" => 0x1d,
+        "shl" => SHL,
+        "shr" => SHR,
+        "sar" => SAR,
+        "clz" => CLZ,
 
         // 0x20 range - crypto.
         "keccak256" => 0x20,
@@ -147,7 +177,7 @@ pub fn str_to_op(name: &str) -> Option<OpCode> {
         // 0x60 range - pushes. // all pushes instructions are invalid
 
         // 0x80 range - dups.
-        "dup1" => 0x80,
+        "dup1" => DUP1,
         "dup2" => 0x81,
         "dup3" => 0x82,
         "dup4" => 0x83,
@@ -162,10 +192,10 @@ pub fn str_to_op(name: &str) -> Option<OpCode> {
         "dup13" => 0x8c,
         "dup14" => 0x8d,
         "dup15" => 0x8e,
-        "dup16" => 0x8f,
+        "dup16" => DUP16,
 
         // 0x90 range - swaps.
-        "swap1" => 0x90,
+        "swap1" => SWAP1,
         "swap2" => 0x91,
         "swap3" => 0x92,
         "swap4" => 0x93,
@@ -180,7 +210,7 @@ pub fn str_to_op(name: &str) -> Option<OpCode> {
         "swap13" => 0x9c,
         "swap14" => 0x9d,
         "swap15" => 0x9e,
-        "swap16" => 0x9f,
+        "swap16" => SWAP16,
 
         // 0xa0 range - logging ops.
         "log0" => 0xa0,
@@ -193,12 +223,13 @@ pub fn str_to_op(name: &str) -> Option<OpCode> {
         "create" => 0xf0,
         "call" => 0xf1,
         "callcode" => 0xf2,
-        "return" => 0xf3,
+        "return" => RETURN,
         "delegatecall" => 0xf4,
-        "create2" => 0xf5,
+        "create2" => CREATE2,
 
-        "staticcall" => 0xfa,
-        "revert" => 0xfd,
+        "staticcall" => STATICCALL,
+        "revert" => REVERT,
+        "invalid" => INVALID,
 
         "selfdestruct" => 0xff,
 
@@ -206,9 +237,126 @@ pub fn str_to_op(name: &str) -> Option<OpCode> {
     })
 }
 
+/// Number of items popped and pushed by a built-in opcode, `None` if the opcode is undefined.
+#[rustfmt::skip]
+pub fn stack_effect(op: OpCode) -> Option<(usize, usize)> {
+    Some(match op {
+        0x00 => (0, 0),
+        0x01..=0x07 | 0x0a | 0x0b => (2, 1),
+        0x08 | 0x09 => (3, 1),
+        0x10..=0x14 | 0x16..=0x18 | 0x1a..=0x1d => (2, 1),
+        0x15 | NOT | CLZ => (1, 1),
+        0x20 => (2, 1),
+        ADDRESS | ORIGIN | CALLER | CALLVALUE | CALLDATASIZE | CODESIZE | GASPRICE |
+        RETURNDATASIZE => (0, 1),
+        BALANCE | CALLDATALOAD | EXTCODESIZE | EXTCODEHASH => (1, 1),
+        CALLDATACOPY | CODECOPY | RETURNDATACOPY => (3, 0),
+        EXTCODECOPY => (4, 0),
+        BLOCKHASH | BLOBHASH => (1, 1),
+        COINBASE..=BASEFEE | BLOBBASEFEE => (0, 1),
+        POP | JUMP => (1, 0),
+        MLOAD | SLOAD | TLOAD => (1, 1),
+        MSTORE | MSTORE8 | SSTORE | JUMPI | TSTORE => (2, 0),
+        PC | MSIZE | GAS => (0, 1),
+        JUMPDEST => (0, 0),
+        MCOPY => (3, 0),
+        PUSH0..=PUSH32 => (0, 1),
+        DUP1..=DUP16 => {
+            let n = (op - DUP1 + 1) as usize;
+            (n, n + 1)
+        }
+        SWAP1..=SWAP16 => {
+            let n = (op - SWAP1 + 2) as usize;
+            (n, n)
+        }
+        0xa0..=0xa4 => ((op - 0xa0 + 2) as usize, 0),
+        0xf0 => (3, 1),
+        0xf1 | 0xf2 => (7, 1),
+        RETURN | REVERT => (2, 0),
+        0xf4 | STATICCALL => (6, 1),
+        CREATE2 => (4, 1),
+        INVALID => (0, 0),
+        0xff => (1, 0),
+        _ => return None,
+    })
+}
+
+/// Whether the execution never continues after the opcode, undefined opcodes being invalid.
+pub fn is_terminating(op: OpCode) -> bool {
+    matches!(op, 0x00 | JUMP | RETURN | REVERT | INVALID | 0xff) || stack_effect(op).is_none()
+}
+
 pub fn push_length(op: u8) -> Option<usize> {
     match op {
         PUSH0..=PUSH32 => Some((op - PUSH0) as usize),
         _ => None,
     }
 }
+
+/// Opcode declared for a chain adding or repurposing opcodes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct CustomOpcode {
+    pub name: String,
+    pub opcode: OpCode,
+    /// Number of items popped from the stack.
+    #[serde(default)]
+    pub inputs: usize,
+    /// Number of items pushed on the stack.
+    #[serde(default)]
+    pub outputs: usize,
+}
+
+/// The custom opcodes accepted in addition to the built-in ones, by lowercase name.
+#[derive(Debug, Clone, Default)]
+pub struct Opcodes {
+    custom: HashMap<String, CustomOpcode>,
+}
+
+impl Opcodes {
+    pub fn custom(&self, name: &str) -> Option<&CustomOpcode> {
+        self.custom.get(&name.to_lowercase())
+    }
+
+    pub fn is_custom(&self, op: OpCode) -> bool {
+        self.custom.values().any(|custom| custom.opcode == op)
+    }
+
+    /// Number of items popped and pushed by an opcode, the custom opcodes overriding the built-in
+    /// ones.
+    pub fn stack_effect(&self, op: OpCode) -> Option<(usize, usize)> {
+        match self.custom.values().find(|custom| custom.opcode == op) {
+            Some(custom) => Some((custom.inputs, custom.outputs)),
+            None => stack_effect(op),
+        }
+    }
+
+    /// Whether the execution never continues after the opcode.
+    pub fn is_terminating(&self, op: OpCode) -> bool {
+        !self.is_custom(op) && is_terminating(op)
+    }
+
+    /// Registers a custom opcode, or returns why it cannot be.
+    pub fn add(&mut self, mut custom: CustomOpcode) -> Result<(), String> {
+        custom.name = custom.name.to_lowercase();
+        let mut chars = custom.name.chars();
+        if !chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            || !chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(format!("Invalid opcode name `{}`.", custom.name));
+        }
+        if str_to_op(&custom.name).is_some() || self.custom.contains_key(&custom.name) {
+            return Err(format!("Name `{}` already used", custom.name));
+        }
+        if push_length(custom.opcode).is_some_and(|length| length > 0) {
+            return Err(format!(
+                "The opcode 0x{:02x} is a push, it cannot be redefined.",
+                custom.opcode
+            ));
+        }
+        self.custom.insert(custom.name.clone(), custom);
+        Ok(())
+    }
+}
@@ -3,19 +3,26 @@ use std::ops::Deref;
 
 use bytes::Bytes;
 
-use super::attribute::Attribute;
+use super::attribute::custom_opcode_from_r_attribute;
+use super::attribute::{Assertion, Attribute, StackAnnotation};
 use super::block_flow::{
-    BlockFlow, BlockFlowBlockRef, BlockFlowItem, BlockFlowPush, BlockFlowPushInner,
-    analyze_block_flow, is_function_name,
+    BlockFlow, BlockFlowBlockRef, BlockFlowItem, BlockFlowLabel, BlockFlowPush, BlockFlowPushInner,
+    BlockFlowTableEntry, Labels, analyze_block_flow, is_function_name,
 };
-use super::opcode::str_to_op;
+use super::cfg::strip_cfg;
+use super::evm_version::EvmVersion;
+use super::expression::{Expression, Value, evaluate_expression};
+use super::inheritance::ContractFlattener;
+use super::opcode::{CustomOpcode, Opcodes, str_to_op};
 use super::queue::PersistentDedupQueue;
 use super::remapping::remap_contracts;
+use super::stack::{check_stack, resolve_stack_slots};
 use crate::ast::*;
 use crate::parser::error::{
     PestError, new_error_from_located, new_error_from_location, new_generic_error,
 };
-use crate::parser::parser::Located;
+use crate::parser::parser::{Located, Location};
+use crate::parser::source::Sources;
 use crate::pre_processing::attribute::Attributes;
 use crate::pre_processing::dependencies::DepsGraph;
 use crate::pre_processing::remapping::remap_blocks;
@@ -27,12 +34,48 @@ pub struct Contract {
     pub name: String,
     #[allow(unused)]
     pub last: bool,
+    pub assertions: Vec<LayoutAssertion>,
+    pub evm_version: EvmVersion,
+    /// Names of the immutables declared by the contract.
+    pub immutables: Vec<String>,
+    /// Names of the libraries linked by the contract.
+    pub links: Vec<String>,
+    /// Whether the generated deployment code keeps the constructor arguments after the code.
+    pub constructor_args: bool,
+}
+
+/// Name pushed as zeros, whose value is patched after the compilation.
+#[derive(Clone, Copy, Debug)]
+pub enum Placeholder {
+    Immutable { width: usize },
+    Link,
+}
+
+/// Assertion on the pc or the size of a block, or on the code size when `block_name` is `None`.
+#[derive(Clone, Debug)]
+pub struct LayoutAssertion {
+    pub block_name: Option<String>,
+    pub assertion: Assertion,
 }
 
 #[derive(Clone, Default, Debug)]
 pub struct Block {
     pub name: String,
     pub items: Vec<BlockItem>,
+    pub labels: Vec<BlockLabel>,
+    /// Whether the block only holds data, never followed by filler bytes.
+    pub data: bool,
+    /// Whether the block is a table, whose entries are never read as opcodes.
+    pub table: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockLabel {
+    /// Name of the non-abstract block in which the label is reported.
+    pub block_name: String,
+    pub name: String,
+    /// Index of the item following the label, in the root block.
+    pub line: usize,
 }
 
 #[derive(Clone, Debug)]
@@ -64,13 +107,26 @@ impl Deref for BlockItem {
 pub enum BlockItemInner {
     Bytes(Bytes),
     Contract(usize),
+    Initcode(usize),
     Push(Push),
+    TableEntry(TableEntry),
+}
+
+/// Value written on a fixed number of bytes, without push opcode.
+#[derive(Clone, Debug)]
+pub struct TableEntry {
+    pub inner: PushInner,
+    pub width: usize,
+    pub location: Location,
 }
 
 #[derive(Clone, Debug)]
 pub struct Push {
     pub attributes: Attributes,
     pub inner: PushInner,
+    /// Exact number of bytes pushed, disabling the optimizations.
+    pub width: Option<Located<usize>>,
+    pub location: Location,
 }
 
 #[derive(Clone, Debug)]
@@ -85,37 +141,90 @@ pub enum PushInner {
         index: usize,
         line: usize,
     },
+    /// Replaced by a [`PushInner::BlockPc`] once the position of the label is known.
+    LabelPc {
+        index: usize,
+        location: Location,
+    },
+    /// Expression over block offsets, evaluated once the contract is laid out.
+    Expression(Box<Located<Expression<PushInner>>>),
+    Immutable {
+        name: String,
+        width: usize,
+    },
+    /// Address of a library, pushed on 20 bytes.
+    Link(String),
+    /// Size of the deployment code of a contract compiled before.
+    InitcodeSize {
+        contract_index: usize,
+    },
+    /// Offsets of the references to an immutable of a contract compiled before.
+    ImmutableOffsets {
+        contract_index: usize,
+        name: String,
+        location: Location,
+    },
 }
 
 pub fn pre_process(
-    input: &str,
-    r_file: RFile,
+    input: &Sources,
+    mut r_file: RFile,
     contract_name: &str,
+    evm_version: EvmVersion,
+    custom_opcodes: &[CustomOpcode],
     compile_variables: &HashMap<String, Bytes>,
+    stack_analysis: bool,
 ) -> Result<Vec<Contract>, PestError> {
+    let mut opcodes = Opcodes::default();
+    for custom_opcode in custom_opcodes {
+        if is_function_name(&custom_opcode.name) {
+            return Err(new_generic_error(format!(
+                "Name `{}` already used",
+                custom_opcode.name
+            )));
+        }
+        opcodes
+            .add(custom_opcode.clone())
+            .map_err(new_generic_error)?;
+    }
+    // a contract excluded by `cfg` does not declare its opcodes
+    strip_cfg(input, &mut r_file, compile_variables)?;
+
+    // `#[opcode(...)]` declares an opcode for the whole file like `#![opcode(...)]`, even though it
+    // is parsed as an attribute of the contract below it
+    for r_contract_with_attr in &mut r_file.contracts {
+        let (declarations, attributes) = std::mem::take(&mut r_contract_with_attr.inner.attributes)
+            .into_iter()
+            .partition(|r_attribute| r_attribute.name_str() == "opcode");
+        r_contract_with_attr.inner.attributes = attributes;
+        r_file.attributes.extend::<Vec<_>>(declarations);
+    }
+    for r_attribute in &r_file.attributes {
+        let custom_opcode = custom_opcode_from_r_attribute(input, r_attribute, compile_variables)?;
+        let name = &r_attribute.arg().unwrap().location;
+        if is_function_name(&custom_opcode.name) {
+            return Err(new_error_from_location(
+                input,
+                name,
+                &format!("Name `{}` already used", custom_opcode.name),
+            ));
+        }
+        if let Err(err) = opcodes.add(custom_opcode) {
+            return Err(new_error_from_location(input, name, &err));
+        }
+    }
+    let opcodes = opcodes;
+
     let mut main_index: Option<usize> = None;
     let mut contract_names = HashMap::<String, usize>::new();
-    let mut contract_attributes = vec![Attributes::default(); r_file.0.len()];
-
-    for (contract_index, r_contract_with_attr) in r_file.0.iter().enumerate() {
-        for r_attribute in &r_contract_with_attr.attributes {
-            let attribute = Attribute::from_r_attribute(input, r_attribute, compile_variables)?;
-            if attribute.is_contract_attribute() {
-                contract_attributes[contract_index].apply(attribute);
-            } else {
-                return Err(new_error_from_located(
-                    input,
-                    r_attribute,
-                    "Invalid contract attribute",
-                ));
-            }
-        }
 
+    for (contract_index, r_contract_with_attr) in r_file.contracts.iter().enumerate() {
         let r_contract = &r_contract_with_attr.inner().inner;
         let name = r_contract.name_str();
-        if contract_names
-            .insert(name.to_owned(), contract_names.len())
-            .is_some()
+        if opcodes.custom(name).is_some()
+            || contract_names
+                .insert(name.to_owned(), contract_names.len())
+                .is_some()
         {
             return Err(new_error_from_located(
                 input,
@@ -141,17 +250,68 @@ pub fn pre_process(
     contracts_queue.insert_if_needed(main_index);
 
     let mut contracts_dependency_tree = DepsGraph::<usize>::new();
+    let mut flattener = ContractFlattener::new(input, &r_file.contracts, &contract_names);
 
     while let Some(index_to_process) = contracts_queue.pop() {
-        // tracing::info!("Pre-processing contract {}", &r_file.0[index_to_process].inner().name_str());
+        // tracing::info!("Pre-processing contract {}", &r_file.contracts[index_to_process].inner().name_str());
         contracts_dependency_tree.add_node_if_needed(&index_to_process);
-        let (contract, dependencies) = pre_process_contract(
+        let r_contract_with_attr = flattener.flatten(index_to_process)?;
+
+        // the EVM version of the contract is needed to check the other attributes
+        let mut contract_evm_version = evm_version;
+        for r_attribute in &r_contract_with_attr.attributes {
+            if r_attribute.name_str() == "evm_version"
+                && let Attribute::EvmVersion(version) =
+                    Attribute::from_r_attribute(input, r_attribute, evm_version, compile_variables)?
+            {
+                contract_evm_version = version;
+            }
+        }
+
+        let mut contract_attributes = Attributes::default();
+        let mut contract_assertions = Vec::<Assertion>::new();
+        let mut constructor_args = false;
+        for r_attribute in &r_contract_with_attr.attributes {
+            let attribute = Attribute::from_r_attribute(
+                input,
+                r_attribute,
+                contract_evm_version,
+                compile_variables,
+            )?;
+            if let Attribute::Assert(assertion) = attribute {
+                if !assertion.subject.is_codesize() {
+                    return Err(new_error_from_located(
+                        input,
+                        r_attribute,
+                        "Only `codesize` can be asserted on a contract.",
+                    ));
+                }
+                contract_assertions.push(assertion);
+            } else if attribute.is_constructor_args() {
+                constructor_args = true;
+            } else if attribute.is_contract_attribute() {
+                contract_attributes.apply(attribute);
+            } else {
+                return Err(new_error_from_located(
+                    input,
+                    r_attribute,
+                    "Invalid contract attribute",
+                ));
+            }
+        }
+
+        let (mut contract, dependencies) = pre_process_contract(
             input,
-            &r_file.0[index_to_process],
-            &contract_attributes[index_to_process],
+            r_contract_with_attr,
+            &contract_attributes,
+            contract_assertions,
+            contract_evm_version,
+            &opcodes,
             &contract_names,
             compile_variables,
+            stack_analysis,
         )?;
+        contract.constructor_args = constructor_args;
 
         for dependency in dependencies {
             contracts_queue.insert_if_needed(dependency);
@@ -161,14 +321,21 @@ pub fn pre_process(
         contracts.insert(index_to_process, contract);
     }
 
-    for index in 0..r_file.0.len() {
-        if !contracts.contains_key(&index) {
+    for index in 0..r_file.contracts.len() {
+        // imported files are libraries, only the unused contracts of the compiled file are reported
+        if !contracts.contains_key(&index)
+            && !flattener.is_base(index)
+            && r_file.contracts[index].location.file == 0
+        {
             tracing::warn!(
                 "{}",
                 new_error_from_located(
                     input,
-                    r_file.0[index].inner(),
-                    &format!("Unused contract `{}`", r_file.0[index].inner().name_str())
+                    r_file.contracts[index].inner(),
+                    &format!(
+                        "Unused contract `{}`",
+                        r_file.contracts[index].inner().name_str()
+                    )
                 )
             );
         }
@@ -190,23 +357,39 @@ pub fn pre_process(
     Ok(remap_contracts(contracts, &remapping_indexes))
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn pre_process_contract(
-    input: &str,
+    input: &Sources,
     r_contract_with_attr: &Located<WithAttributes<Located<RContract>>>,
     default_attributes: &Attributes,
+    contract_assertions: Vec<Assertion>,
+    evm_version: EvmVersion,
+    opcodes: &Opcodes,
     contract_names: &HashMap<String, usize>,
     compile_variables: &HashMap<String, Bytes>,
+    stack_analysis: bool,
 ) -> Result<(Contract, HashSet<usize>), PestError> {
     let r_contract = &r_contract_with_attr.inner.inner;
 
     let constants = extract_constants(
         input,
         &r_contract.constants,
+        opcodes,
         contract_names,
         compile_variables,
     )?;
 
+    let placeholders = extract_placeholders(
+        input,
+        &r_contract.immutables,
+        &r_contract.links,
+        &constants,
+        opcodes,
+        contract_names,
+    )?;
+
     let mut block_attributes = vec![Vec::<Attribute>::new(); r_contract.blocks.len()];
+    let mut stack_annotations = Vec::<(usize, StackAnnotation)>::new();
 
     let mut main_index: Option<usize> = None;
     let mut last_index: Option<usize> = None;
@@ -214,12 +397,45 @@ pub fn pre_process_contract(
 
     let mut blocks_queue = PersistentDedupQueue::<usize>::new();
 
+    let mut assertions = contract_assertions
+        .into_iter()
+        .map(|assertion| LayoutAssertion {
+            block_name: None,
+            assertion,
+        })
+        .collect::<Vec<_>>();
+
     for (block_index, r_block_with_attr) in r_contract.blocks.iter().enumerate() {
         for r_attribute in &r_block_with_attr.attributes {
-            let attribute = Attribute::from_r_attribute(input, r_attribute, compile_variables)?;
+            let attribute =
+                Attribute::from_r_attribute(input, r_attribute, evm_version, compile_variables)?;
+            if r_block_with_attr.inner().data && (attribute.is_main() || attribute.is_last()) {
+                return Err(new_error_from_located(
+                    input,
+                    r_attribute,
+                    "Data blocks are always placed after the code.",
+                ));
+            }
+            if let Attribute::Stack(annotation) = attribute {
+                if r_block_with_attr.inner().data || r_block_with_attr.inner().table_width.is_some()
+                {
+                    return Err(new_error_from_located(
+                        input,
+                        r_attribute,
+                        "The attribute `stack` only applies to blocks of code.",
+                    ));
+                }
+                stack_annotations.push((block_index, annotation));
+                continue;
+            }
             if !r_block_with_attr.inner().abstr {
                 if attribute.is_block_attribute() {
-                    if attribute.is_last() {
+                    if let Attribute::Assert(assertion) = attribute {
+                        assertions.push(LayoutAssertion {
+                            block_name: Some(r_block_with_attr.inner().name_str().to_owned()),
+                            assertion,
+                        });
+                    } else if attribute.is_last() {
                         blocks_queue.insert_if_needed(block_index);
                         if last_index.replace(block_index).is_some() {
                             return Err(new_error_from_located(
@@ -263,7 +479,9 @@ pub fn pre_process_contract(
         let block_name = r_block.name_str();
 
         if contract_names.contains_key(block_name)
+            || opcodes.custom(block_name).is_some()
             || constants.contains_key(block_name)
+            || placeholders.contains_key(block_name)
             || block_names
                 .insert(block_name.to_owned(), block_names.len())
                 .is_some()
@@ -274,6 +492,13 @@ pub fn pre_process_contract(
                 &format!("Name `{}` already used", block_name),
             ));
         }
+        if block_name == "main" && r_block.data {
+            return Err(new_error_from_located(
+                input,
+                &r_block.name,
+                "Data blocks are always placed after the code.",
+            ));
+        }
         if block_name == "main" && main_index.replace(block_index).is_some() {
             return Err(new_error_from_located(
                 input,
@@ -281,13 +506,76 @@ pub fn pre_process_contract(
                 "A block is already marked as main.",
             ));
         }
-        if r_block.items.is_empty() {
+        if r_block
+            .items
+            .iter()
+            .all(|r_item| matches!(r_item.inner().inner, RBlockItem::Label(_)))
+        {
             return Err(new_error_from_located(
                 input,
                 &r_block.name,
                 "A block must not be empty.",
             ));
         }
+        if !r_block.abstr && !r_block.params.is_empty() {
+            return Err(new_error_from_located(
+                input,
+                &r_block.name,
+                "Only abstract blocks can take parameters.",
+            ));
+        }
+        for (param_index, param) in r_block.params.iter().enumerate() {
+            let param_name = param.as_str();
+            if str_to_op(param_name).is_some()
+                || opcodes.custom(param_name).is_some()
+                || is_function_name(param_name)
+            {
+                return Err(new_error_from_located(
+                    input,
+                    param,
+                    "Invalid parameter name.",
+                ));
+            }
+            if r_block.params[..param_index]
+                .iter()
+                .any(|previous| previous.as_str() == param_name)
+            {
+                return Err(new_error_from_located(
+                    input,
+                    param,
+                    &format!("Name `{}` already used", param_name),
+                ));
+            }
+        }
+    }
+
+    let mut labels = Labels::default();
+    for r_block_with_attr in &r_contract.blocks {
+        let r_block = r_block_with_attr.inner();
+        let mut block_labels = HashMap::<String, usize>::new();
+        for r_item_with_attr in &r_block.items {
+            let RBlockItem::Label(r_label) = &r_item_with_attr.inner().inner else {
+                continue;
+            };
+            let label_name = r_label.name_str();
+            if contract_names.contains_key(label_name)
+                || opcodes.custom(label_name).is_some()
+                || constants.contains_key(label_name)
+                || placeholders.contains_key(label_name)
+                || block_names.contains_key(label_name)
+                || block_labels
+                    .insert(label_name.to_owned(), labels.in_abstract.len())
+                    .is_some()
+            {
+                return Err(new_error_from_located(
+                    input,
+                    &r_label.name,
+                    &format!("Name `{}` already used", label_name),
+                ));
+            }
+            labels.in_abstract.push(r_block.abstr);
+        }
+        labels.by_block.push(block_labels);
     }
 
     let main_index = main_index;
@@ -317,9 +605,13 @@ pub fn pre_process_contract(
             input,
             &r_contract.blocks[index_to_process],
             &constants,
+            &placeholders,
             contract_names,
             &block_names,
+            &labels,
             &mut contract_dependencies,
+            evm_version,
+            opcodes,
             compile_variables,
         )?;
 
@@ -333,6 +625,7 @@ pub fn pre_process_contract(
 
         blocks_flow.insert(index_to_process, block);
     }
+    resolve_stack_slots(input, &mut blocks_flow, opcodes, &stack_annotations)?;
     let blocks_flow = blocks_flow;
 
     for block_index in 0..r_contract.blocks.len() {
@@ -363,11 +656,18 @@ pub fn pre_process_contract(
     let mut blocks = HashMap::<usize, Block>::new();
     let mut unique_dereferences = HashSet::<usize>::new();
     let mut new_positions = HashMap::<usize, BlockPosition>::new();
+    let mut label_positions = HashMap::<usize, BlockPosition>::new();
     let mut remapping = Vec::<usize>::new();
     remapping.push(main_index);
+    // data blocks are placed after all the code
+    let mut data_remapping = Vec::<usize>::new();
 
     while let Some(index_to_process) = blocks_queue.pop() {
-        if index_to_process != main_index && index_to_process != last_index.unwrap_or(main_index) {
+        if r_contract.blocks[index_to_process].inner().data {
+            data_remapping.push(index_to_process);
+        } else if index_to_process != main_index
+            && index_to_process != last_index.unwrap_or(main_index)
+        {
             remapping.push(index_to_process);
         }
 
@@ -382,6 +682,7 @@ pub fn pre_process_contract(
             &block_attributes,
             &mut unique_dereferences,
             &mut new_positions,
+            &mut label_positions,
         )?;
 
         blocks.insert(index_to_process, block);
@@ -389,38 +690,70 @@ pub fn pre_process_contract(
     if let Some(last_index) = last_index {
         remapping.push(last_index);
     }
+    remapping.append(&mut data_remapping);
+
+    check_stack(
+        input,
+        &blocks_flow,
+        &r_contract.blocks,
+        &labels,
+        &remapping,
+        opcodes,
+        &stack_annotations,
+        stack_analysis,
+    )?;
+
+    for block in blocks.values() {
+        for item in &block.items {
+            match &item.inner {
+                BlockItemInner::Push(Push { inner, .. })
+                | BlockItemInner::TableEntry(TableEntry { inner, .. }) => {
+                    check_label_positions(input, inner, &label_positions)?;
+                }
+                _ => (),
+            }
+        }
+    }
 
     Ok((
         Contract {
             name: r_contract.name_str().to_owned(),
-            blocks: remap_blocks(blocks, &remapping, &new_positions),
+            blocks: remap_blocks(blocks, &remapping, &new_positions, &label_positions),
             last: last_index.is_some(),
+            assertions,
+            evm_version,
+            immutables: r_contract
+                .immutables
+                .iter()
+                .map(|r_immutable| r_immutable.name_str().to_owned())
+                .collect(),
+            links: r_contract
+                .links
+                .iter()
+                .map(|r_link| r_link.name_str().to_owned())
+                .collect(),
+            constructor_args: false,
         },
         contract_dependencies,
     ))
 }
 
 pub fn extract_constants(
-    input: &str,
-    r_constants: &Vec<Located<RConstant>>,
+    input: &Sources,
+    r_constants: &[Located<RConstant>],
+    opcodes: &Opcodes,
     contract_names: &HashMap<String, usize>,
     compile_variables: &HashMap<String, Bytes>,
-) -> Result<HashMap<String, Bytes>, PestError> {
-    let mut constants = HashMap::<String, Bytes>::new();
+) -> Result<HashMap<String, Value>, PestError> {
+    let mut constant_names = HashMap::<String, usize>::new();
 
-    for r_constant in r_constants {
+    for (index, r_constant) in r_constants.iter().enumerate() {
         let constant_name = r_constant.name_str();
 
-        let value = match &r_constant.value.inner {
-            RConstantArg::HexLiteral(hex_literal) => hex_literal.0.clone(),
-            RConstantArg::CompileVariable(compile_variable) => {
-                get_compile_variable_value(input, compile_variable, compile_variables)?.clone()
-            }
-        };
-
         if contract_names.contains_key(constant_name)
-            || constants
-                .insert(constant_name.to_owned(), value.clone())
+            || opcodes.custom(constant_name).is_some()
+            || constant_names
+                .insert(constant_name.to_owned(), index)
                 .is_some()
         {
             return Err(new_error_from_located(
@@ -439,14 +772,132 @@ pub fn extract_constants(
         }
     }
 
+    // Constants can reference each other in any order, they are evaluated on demand.
+    let mut constants = HashMap::<String, Value>::new();
+    for index in 0..r_constants.len() {
+        evaluate_constant(
+            input,
+            index,
+            r_constants,
+            &constant_names,
+            &mut constants,
+            &mut Vec::new(),
+            compile_variables,
+        )?;
+    }
+
     Ok(constants)
 }
 
+/// Returns the immutables and links of a contract, by name.
+pub fn extract_placeholders(
+    input: &Sources,
+    r_immutables: &[Located<RImmutable>],
+    r_links: &[Located<RLink>],
+    constants: &HashMap<String, Value>,
+    opcodes: &Opcodes,
+    contract_names: &HashMap<String, usize>,
+) -> Result<HashMap<String, Placeholder>, PestError> {
+    let mut placeholders = HashMap::<String, Placeholder>::new();
+
+    let declarations = r_immutables
+        .iter()
+        .map(|r_immutable| {
+            (
+                &r_immutable.name,
+                Placeholder::Immutable {
+                    width: r_immutable.width,
+                },
+            )
+        })
+        .chain(
+            r_links
+                .iter()
+                .map(|r_link| (&r_link.name, Placeholder::Link)),
+        );
+
+    for (name, placeholder) in declarations {
+        let placeholder_name = name.as_str();
+
+        if str_to_op(placeholder_name).is_some() || is_function_name(placeholder_name) {
+            return Err(new_error_from_located(input, name, "Invalid name."));
+        }
+
+        if contract_names.contains_key(placeholder_name)
+            || opcodes.custom(placeholder_name).is_some()
+            || constants.contains_key(placeholder_name)
+            || placeholders
+                .insert(placeholder_name.to_owned(), placeholder)
+                .is_some()
+        {
+            return Err(new_error_from_located(
+                input,
+                name,
+                &format!("Name `{}` already used", placeholder_name),
+            ));
+        }
+    }
+
+    Ok(placeholders)
+}
+
+fn evaluate_constant(
+    input: &Sources,
+    index: usize,
+    r_constants: &[Located<RConstant>],
+    constant_names: &HashMap<String, usize>,
+    constants: &mut HashMap<String, Value>,
+    stack: &mut Vec<usize>,
+    compile_variables: &HashMap<String, Bytes>,
+) -> Result<Value, PestError> {
+    let r_constant = &r_constants[index];
+    if let Some(value) = constants.get(r_constant.name_str()) {
+        return Ok(value.clone());
+    }
+
+    stack.push(index);
+    let value = evaluate_expression(
+        input,
+        &r_constant.value,
+        &mut |name, location| {
+            let Some(dependency) = constant_names.get(name).copied() else {
+                return Err(new_error_from_location(
+                    input,
+                    location,
+                    &format!("Unknown constant `{}`.", name),
+                ));
+            };
+            if stack.contains(&dependency) {
+                return Err(new_error_from_location(
+                    input,
+                    location,
+                    "Recursive constant definition.",
+                ));
+            }
+            evaluate_constant(
+                input,
+                dependency,
+                r_constants,
+                constant_names,
+                constants,
+                stack,
+                compile_variables,
+            )
+        },
+        compile_variables,
+    )?;
+    stack.pop();
+
+    constants.insert(r_constant.name_str().to_owned(), value.clone());
+    Ok(value)
+}
+
 #[derive(Clone, Debug)]
 pub struct BlockPreProcessingContext {
     pub root_index: usize,
     pub inside_abstract: bool,
     pub line_index: usize,
+    pub args: Vec<Located<Expression<BlockFlowPushInner>>>,
 }
 
 #[derive(Clone, Debug)]
@@ -462,21 +913,106 @@ impl BlockPreProcessingContext {
             root_index: index,
             inside_abstract: false,
             line_index: 0,
+            args: Vec::new(),
         }
     }
 
-    pub fn next_context(&self, inside_abstract: bool, line_index: usize) -> Self {
+    pub fn next_context(
+        &self,
+        inside_abstract: bool,
+        line_index: usize,
+        args: Vec<Located<Expression<BlockFlowPushInner>>>,
+    ) -> Self {
         Self {
             root_index: self.root_index,
             inside_abstract,
             line_index: self.line_index + line_index,
+            args,
+        }
+    }
+
+    /// Replaces the parameters by the arguments given to the block being expanded.
+    pub fn substitute(
+        &self,
+        expression: Located<Expression<BlockFlowPushInner>>,
+    ) -> Located<Expression<BlockFlowPushInner>> {
+        expression.map_leaves(&mut |leaf| match leaf {
+            BlockFlowPushInner::Parameter(index) => self.args[index].inner.clone(),
+            leaf => Expression::Leaf(leaf),
+        })
+    }
+
+    fn push_inner(
+        &self,
+        input: &Sources,
+        push: &BlockFlowPushInner,
+    ) -> Result<PushInner, PestError> {
+        let expression = match push {
+            BlockFlowPushInner::Constant(bytes) => return Ok(PushInner::Constant(bytes.clone())),
+            BlockFlowPushInner::Parameter(index) => self.args[*index].clone().fold(input)?,
+            BlockFlowPushInner::Expression(expression) => {
+                self.substitute(expression.as_ref().clone()).fold(input)?
+            }
+            leaf => return Ok(leaf_push_inner(leaf.clone())),
+        };
+
+        // immutables and links are patched in place, and the fields of the other contracts are
+        // only known when compiling, they cannot be combined
+        let mut combines_direct_push = false;
+        if !matches!(expression.inner, Expression::Leaf(_)) {
+            expression
+                .for_each_leaf(&mut |leaf| combines_direct_push |= leaf.must_be_pushed_directly());
+        }
+        if combines_direct_push {
+            return Err(new_error_from_located(
+                input,
+                &expression,
+                "Immutables, links and contract fields can only be pushed directly.",
+            ));
+        }
+
+        Ok(match expression.inner {
+            Expression::Value(value) => {
+                PushInner::Constant(Bytes32::from_bytes(&value.bytes, true).unwrap())
+            }
+            Expression::Leaf(leaf) => leaf_push_inner(leaf),
+            _ => PushInner::Expression(Box::new(
+                expression.map_leaves(&mut |leaf| Expression::Leaf(leaf_push_inner(leaf))),
+            )),
+        })
+    }
+}
+
+fn leaf_push_inner(leaf: BlockFlowPushInner) -> PushInner {
+    match leaf {
+        BlockFlowPushInner::BlockPc(index) => PushInner::BlockPc { index, line: 0 },
+        BlockFlowPushInner::BlockSize(index) => PushInner::BlockSize {
+            index,
+            start: 0,
+            end: 0,
+        },
+        BlockFlowPushInner::LabelPc(index, location) => PushInner::LabelPc { index, location },
+        BlockFlowPushInner::Immutable { name, width } => PushInner::Immutable { name, width },
+        BlockFlowPushInner::Link(name) => PushInner::Link(name),
+        BlockFlowPushInner::InitcodeSize(contract_index) => {
+            PushInner::InitcodeSize { contract_index }
         }
+        BlockFlowPushInner::ImmutableOffsets {
+            contract_index,
+            name,
+            location,
+        } => PushInner::ImmutableOffsets {
+            contract_index,
+            name,
+            location,
+        },
+        _ => unreachable!(),
     }
 }
 
 #[allow(clippy::too_many_arguments)]
 fn pre_process_block(
-    input: &str,
+    input: &Sources,
     index_to_process: usize,
     r_blocks: &Vec<Located<WithAttributes<Located<RBlock>>>>,
     blocks_flow: &HashMap<usize, BlockFlow>,
@@ -486,51 +1022,96 @@ fn pre_process_block(
     block_attributes: &Vec<Vec<Attribute>>,
     unique_dereferences: &mut HashSet<usize>,
     new_positions: &mut HashMap<usize, BlockPosition>,
+    label_positions: &mut HashMap<usize, BlockPosition>,
 ) -> Result<Block, PestError> {
     // tracing::info!("Pre-processing block {}", &r_blocks[index_to_process].inner().name_str());
 
     current_attributes.apply_many(block_attributes[index_to_process].clone());
 
+    let block_name = r_blocks[index_to_process].name_str();
     let mut items = Vec::<BlockItem>::new();
+    let mut labels = Vec::<BlockLabel>::new();
 
     let block_flow = blocks_flow.get(&index_to_process).unwrap();
 
     for block_flow_item in &block_flow.items {
         match block_flow_item {
-            BlockFlowItem::Bytes(bytes) => items.push(BlockItemInner::Bytes(bytes.clone()).into()),
+            BlockFlowItem::Label(BlockFlowLabel {
+                index,
+                name,
+                location,
+            }) => {
+                let line = context.line_index + items.len();
+                let position = BlockPosition {
+                    root_index: context.root_index,
+                    start: line,
+                    end: line,
+                };
+                if label_positions.insert(*index, position).is_some() {
+                    return Err(new_error_from_location(
+                        input,
+                        location,
+                        "Labels cannot be declared in an abstract block expanded more than once.",
+                    ));
+                }
+                labels.push(BlockLabel {
+                    block_name: block_name.to_owned(),
+                    name: name.clone(),
+                    line,
+                });
+            }
+            BlockFlowItem::Bytes(bytes) => {
+                items.push(BlockItemInner::Bytes(bytes.inner.clone()).into());
+            }
             BlockFlowItem::Contract(contract_index) => {
                 items.push(BlockItemInner::Contract(*contract_index).into());
             }
-            BlockFlowItem::Push(BlockFlowPush { attributes, inner }) => {
+            BlockFlowItem::Initcode(contract_index) => {
+                items.push(BlockItemInner::Initcode(*contract_index).into());
+            }
+            BlockFlowItem::Push(BlockFlowPush {
+                attributes,
+                inner,
+                width,
+                location,
+            }) => {
                 current_attributes.apply_many(attributes.clone());
                 items.push(
                     BlockItemInner::Push(Push {
                         attributes: current_attributes.clone(),
-                        inner: match inner {
-                            BlockFlowPushInner::Constant(bytes) => {
-                                PushInner::Constant(bytes.clone())
-                            }
-                            BlockFlowPushInner::BlockPc(index) => PushInner::BlockPc {
-                                index: *index,
-                                line: 0,
-                            },
-                            BlockFlowPushInner::BlockSize(index) => PushInner::BlockSize {
-                                index: *index,
-                                start: 0,
-                                end: 0,
-                            },
-                        },
+                        inner: context.push_inner(input, inner)?,
+                        width: width.clone(),
+                        location: location.clone(),
                     })
                     .into(),
                 );
             }
+            BlockFlowItem::TableEntry(BlockFlowTableEntry {
+                inner,
+                width,
+                location,
+            }) => {
+                items.push(
+                    BlockItemInner::TableEntry(TableEntry {
+                        inner: context.push_inner(input, inner)?,
+                        width: *width,
+                        location: location.clone(),
+                    })
+                    .into(),
+                );
+            }
+            // resolved once all the blocks are analyzed
+            BlockFlowItem::StackSlot(_) | BlockFlowItem::StackLayout(_) => unreachable!(),
             BlockFlowItem::BlockEsp(BlockFlowBlockRef {
                 index: block_index,
                 location,
                 attributes,
+                args,
             }) => {
                 current_attributes.apply_many(attributes.clone());
-                if !r_blocks[*block_index].inner().abstr {
+                let r_block = r_blocks[*block_index].inner();
+                check_not_data(input, r_block, location)?;
+                if !r_block.abstr {
                     return Err(new_error_from_location(
                         input,
                         location,
@@ -538,6 +1119,19 @@ fn pre_process_block(
                     ));
                 }
 
+                if args.len() != r_block.params.len() {
+                    return Err(new_error_from_location(
+                        input,
+                        location,
+                        &format!(
+                            "Block `{}` takes {} argument(s) but {} were given.",
+                            r_block.name_str(),
+                            r_block.params.len(),
+                            args.len()
+                        ),
+                    ));
+                }
+
                 if parents.contains(block_index) {
                     return Err(new_error_from_location(
                         input,
@@ -549,21 +1143,34 @@ fn pre_process_block(
                 parents.insert(*block_index);
                 let Block {
                     items: mut sub_items,
-                    name: _,
+                    labels: sub_labels,
+                    ..
                 } = pre_process_block(
                     input,
                     *block_index,
                     r_blocks,
                     blocks_flow,
-                    context.next_context(true, items.len()),
+                    context.next_context(
+                        true,
+                        items.len(),
+                        args.iter()
+                            .map(|arg| context.substitute(arg.clone()))
+                            .collect(),
+                    ),
                     parents,
                     current_attributes,
                     block_attributes,
                     unique_dereferences,
                     new_positions,
+                    label_positions,
                 )?;
                 parents.remove(block_index);
                 items.append(&mut sub_items);
+                // The labels of an abstract block are reported in the block expanding it.
+                labels.extend(sub_labels.into_iter().map(|label| BlockLabel {
+                    block_name: block_name.to_owned(),
+                    ..label
+                }));
                 current_attributes
                     .apply_many(blocks_flow.get(block_index).unwrap().end_attributes.clone());
             }
@@ -571,8 +1178,10 @@ fn pre_process_block(
                 index: block_index,
                 location,
                 attributes,
+                args: _,
             }) => {
                 current_attributes.apply_many(attributes.clone());
+                check_not_data(input, r_blocks[*block_index].inner(), location)?;
                 if context.inside_abstract {
                     return Err(new_error_from_location(
                         input,
@@ -615,19 +1224,23 @@ fn pre_process_block(
                 let Block {
                     items: mut sub_items,
                     name,
+                    labels: mut sub_labels,
+                    ..
                 } = pre_process_block(
                     input,
                     *block_index,
                     r_blocks,
                     blocks_flow,
-                    context.next_context(false, items.len()),
+                    context.next_context(false, items.len(), Vec::new()),
                     parents,
                     current_attributes,
                     block_attributes,
                     unique_dereferences,
                     new_positions,
+                    label_positions,
                 )?;
                 parents.remove(block_index);
+                labels.append(&mut sub_labels);
                 sub_items
                     .first_mut()
                     .unwrap()
@@ -660,12 +1273,49 @@ fn pre_process_block(
 
     Ok(Block {
         items,
-        name: r_blocks[index_to_process].name_str().to_owned(),
+        name: block_name.to_owned(),
+        labels,
+        data: r_blocks[index_to_process].inner().data,
+        table: r_blocks[index_to_process].inner().table_width.is_some(),
     })
 }
 
+fn check_not_data(input: &Sources, r_block: &RBlock, location: &Location) -> Result<(), PestError> {
+    if r_block.data {
+        return Err(new_error_from_location(
+            input,
+            location,
+            "A data block cannot be inserted in another block, push its `pc` instead.",
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that every label referenced by a push has been expanded.
+fn check_label_positions(
+    input: &Sources,
+    push: &PushInner,
+    label_positions: &HashMap<usize, BlockPosition>,
+) -> Result<(), PestError> {
+    match push {
+        PushInner::LabelPc { index, location } if !label_positions.contains_key(index) => Err(
+            new_error_from_location(input, location, "This label is never expanded."),
+        ),
+        PushInner::Expression(expression) => {
+            let mut res = Ok(());
+            expression.for_each_leaf(&mut |leaf| {
+                if res.is_ok() {
+                    res = check_label_positions(input, leaf, label_positions);
+                }
+            });
+            res
+        }
+        _ => Ok(()),
+    }
+}
+
 pub fn get_compile_variable_value<'a>(
-    input: &'_ str,
+    input: &'_ Sources,
     compile_variable: &'_ RCompileVariable,
     compile_variables: &'a HashMap<String, Bytes>,
 ) -> Result<&'a Bytes, PestError> {
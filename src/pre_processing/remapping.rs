@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 
-use super::pre_processing::{Block, BlockItemInner, BlockPosition, Contract, Push, PushInner};
+use super::pre_processing::{
+    Block, BlockItemInner, BlockPosition, Contract, Push, PushInner, TableEntry,
+};
 
 pub fn remap_contracts(
     mut contracts: HashMap<usize, Contract>,
@@ -11,8 +13,24 @@ pub fn remap_contracts(
     for contract in contracts.values_mut() {
         for block in &mut contract.blocks {
             for item in &mut block.items {
-                if let BlockItemInner::Contract(contract_index) = &mut item.inner {
-                    *contract_index = *remapping_map.get(contract_index).unwrap();
+                match &mut item.inner {
+                    BlockItemInner::Contract(contract_index)
+                    | BlockItemInner::Initcode(contract_index)
+                    | BlockItemInner::Push(Push {
+                        inner:
+                            PushInner::InitcodeSize { contract_index }
+                            | PushInner::ImmutableOffsets { contract_index, .. },
+                        ..
+                    })
+                    | BlockItemInner::TableEntry(TableEntry {
+                        inner:
+                            PushInner::InitcodeSize { contract_index }
+                            | PushInner::ImmutableOffsets { contract_index, .. },
+                        ..
+                    }) => {
+                        *contract_index = *remapping_map.get(contract_index).unwrap();
+                    }
+                    _ => (),
                 }
             }
         }
@@ -25,36 +43,58 @@ pub fn remap_blocks(
     mut blocks: HashMap<usize, Block>,
     remapping: &[usize],
     new_positions: &HashMap<usize, BlockPosition>,
+    label_positions: &HashMap<usize, BlockPosition>,
 ) -> Vec<Block> {
     let remapping_map = vec_to_remapping_map(remapping);
 
     for block in blocks.values_mut() {
         for item in &mut block.items {
-            match &mut item.inner {
-                BlockItemInner::Push(Push {
-                    attributes: _,
-                    inner: PushInner::BlockPc { index, line },
-                }) => {
-                    let position = new_positions.get(index).unwrap();
-                    *line = position.start;
-                    *index = *remapping_map.get(&position.root_index).unwrap();
-                }
-                BlockItemInner::Push(Push {
-                    attributes: _,
-                    inner: PushInner::BlockSize { index, start, end },
-                }) => {
-                    let position = new_positions.get(index).unwrap();
-                    *start = position.start;
-                    *end = position.end;
-                    *index = *remapping_map.get(&position.root_index).unwrap();
-                }
-                _ => (),
+            if let BlockItemInner::Push(Push { inner, .. })
+            | BlockItemInner::TableEntry(TableEntry { inner, .. }) = &mut item.inner
+            {
+                remap_push(inner, &remapping_map, new_positions, label_positions);
             }
         }
     }
     remap(blocks, remapping)
 }
 
+fn remap_push(
+    inner: &mut PushInner,
+    remapping_map: &HashMap<usize, usize>,
+    new_positions: &HashMap<usize, BlockPosition>,
+    label_positions: &HashMap<usize, BlockPosition>,
+) {
+    match inner {
+        PushInner::BlockPc { index, line } => {
+            let position = new_positions.get(index).unwrap();
+            *line = position.start;
+            *index = *remapping_map.get(&position.root_index).unwrap();
+        }
+        PushInner::BlockSize { index, start, end } => {
+            let position = new_positions.get(index).unwrap();
+            *start = position.start;
+            *end = position.end;
+            *index = *remapping_map.get(&position.root_index).unwrap();
+        }
+        PushInner::LabelPc { index, location: _ } => {
+            let position = label_positions.get(index).unwrap();
+            *inner = PushInner::BlockPc {
+                index: *remapping_map.get(&position.root_index).unwrap(),
+                line: position.start,
+            };
+        }
+        PushInner::Expression(expression) => expression.for_each_leaf_mut(&mut |leaf| {
+            remap_push(leaf, remapping_map, new_positions, label_positions)
+        }),
+        PushInner::Constant(_)
+        | PushInner::Immutable { .. }
+        | PushInner::Link(_)
+        | PushInner::InitcodeSize { .. }
+        | PushInner::ImmutableOffsets { .. } => (),
+    }
+}
+
 fn remap<T: std::fmt::Debug>(mut map: HashMap<usize, T>, remapping: &[usize]) -> Vec<T> {
     let res = remapping.iter().map(|x| map.remove(x).unwrap()).collect();
     assert!(map.is_empty());
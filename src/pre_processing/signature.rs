@@ -0,0 +1,217 @@
+use tiny_keccak::{Hasher, Keccak};
+
+pub fn keccak256(bytes: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    hasher.update(bytes);
+    let mut res = [0u8; 32];
+    hasher.finalize(&mut res);
+    res
+}
+
+/// Canonical form of a function or event signature, as hashed to compute selectors and topics.
+/// Whitespaces, parameter names and data locations are removed, and the type aliases (`uint`,
+/// `int`, `byte`, `fixed`, `ufixed`) are replaced by their full name.
+pub fn canonicalize_signature(signature: &str) -> Result<String, String> {
+    let signature = signature.trim();
+
+    let Some(open) = signature.find('(') else {
+        return Err("missing parameter list".to_owned());
+    };
+    if !signature.ends_with(')') {
+        return Err("the signature must end with `)`".to_owned());
+    }
+
+    let name = signature[..open].trim();
+    if !is_identifier(name) {
+        return Err(format!("invalid name `{}`", name));
+    }
+
+    Ok(format!(
+        "{}({})",
+        name,
+        canonicalize_parameters(&signature[open + 1..signature.len() - 1])?
+    ))
+}
+
+fn canonicalize_parameters(parameters: &str) -> Result<String, String> {
+    if parameters.trim().is_empty() {
+        return Ok(String::new());
+    }
+
+    let mut res = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in parameters.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth = depth
+                    .checked_sub(1)
+                    .ok_or_else(|| "unbalanced parentheses".to_owned())?
+            }
+            ',' if depth == 0 => {
+                res.push(canonicalize_parameter(&parameters[start..i])?);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    if depth != 0 {
+        return Err("unbalanced parentheses".to_owned());
+    }
+    res.push(canonicalize_parameter(&parameters[start..])?);
+
+    Ok(res.join(","))
+}
+
+fn canonicalize_parameter(parameter: &str) -> Result<String, String> {
+    let parameter = parameter.trim();
+    if parameter.is_empty() {
+        return Err("empty parameter".to_owned());
+    }
+
+    let (mut res, mut rest) = if parameter.starts_with('(') {
+        let end = matching_paren(parameter)?;
+        (
+            format!("({})", canonicalize_parameters(&parameter[1..end])?),
+            &parameter[end + 1..],
+        )
+    } else {
+        let end = parameter
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(parameter.len());
+        (
+            canonicalize_elementary_type(&parameter[..end])?,
+            &parameter[end..],
+        )
+    };
+
+    loop {
+        rest = rest.trim_start();
+        let Some(after_bracket) = rest.strip_prefix('[') else {
+            break;
+        };
+        let Some(end) = after_bracket.find(']') else {
+            return Err("unclosed array brackets".to_owned());
+        };
+        let length = after_bracket[..end].trim();
+        if !length.is_empty()
+            && (!length.bytes().all(|c| c.is_ascii_digit()) || length.parse::<u64>() == Ok(0))
+        {
+            return Err(format!("invalid array length `{}`", length));
+        }
+        res = format!("{}[{}]", res, length);
+        rest = &after_bracket[end + 1..];
+    }
+
+    // The remaining words can only be a modifier and the name of the parameter.
+    let mut words = rest.split_whitespace().peekable();
+    if words
+        .peek()
+        .is_some_and(|word| matches!(*word, "indexed" | "memory" | "calldata" | "storage"))
+    {
+        words.next();
+    }
+    if let Some(name) = words.next() {
+        if !is_identifier(name) {
+            return Err(format!("unexpected `{}`", name));
+        }
+    }
+    if let Some(word) = words.next() {
+        return Err(format!("unexpected `{}`", word));
+    }
+
+    Ok(res)
+}
+
+fn matching_paren(parameter: &str) -> Result<usize, String> {
+    let mut depth = 0usize;
+    for (i, c) in parameter.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(i);
+                }
+            }
+            _ => (),
+        }
+    }
+    Err("unbalanced parentheses".to_owned())
+}
+
+fn canonicalize_elementary_type(ty: &str) -> Result<String, String> {
+    let canonical = match ty {
+        "address" | "bool" | "string" | "bytes" | "function" => ty.to_owned(),
+        "uint" => "uint256".to_owned(),
+        "int" => "int256".to_owned(),
+        "byte" => "bytes1".to_owned(),
+        "fixed" => "fixed128x18".to_owned(),
+        "ufixed" => "ufixed128x18".to_owned(),
+        _ => {
+            let valid = if let Some(bits) = ty.strip_prefix("uint").or(ty.strip_prefix("int")) {
+                parse_number(bits).is_some_and(is_valid_bits)
+            } else if let Some(length) = ty.strip_prefix("bytes") {
+                parse_number(length).is_some_and(|length| (1..=32).contains(&length))
+            } else if let Some(fixed) = ty.strip_prefix("ufixed").or(ty.strip_prefix("fixed")) {
+                fixed.split_once('x').is_some_and(|(bits, decimals)| {
+                    parse_number(bits).is_some_and(is_valid_bits)
+                        && parse_number(decimals).is_some_and(|decimals| decimals <= 80)
+                })
+            } else {
+                false
+            };
+            if !valid {
+                return Err(format!("unknown type `{}`", ty));
+            }
+            ty.to_owned()
+        }
+    };
+
+    Ok(canonical)
+}
+
+/// Parses a decimal number without leading zeros.
+fn parse_number(s: &str) -> Option<usize> {
+    if s.is_empty() || (s.len() > 1 && s.starts_with('0')) || !s.bytes().all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+    s.parse().ok()
+}
+
+fn is_valid_bits(bits: usize) -> bool {
+    bits % 8 == 0 && (8..=256).contains(&bits)
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_canonicalize_signature() {
+        assert_eq!(
+            canonicalize_signature("transfer(address to, uint amount)").unwrap(),
+            "transfer(address,uint256)"
+        );
+        assert_eq!(
+            canonicalize_signature("f((uint, byte)[2][] memory xs, fixed)").unwrap(),
+            "f((uint256,bytes1)[2][],fixed128x18)"
+        );
+        assert!(canonicalize_signature("f(uint7)").is_err());
+        assert!(canonicalize_signature("f(bytes33)").is_err());
+        assert_eq!(
+            hex::encode(&keccak256(b"transfer(address,uint256)")[..4]),
+            "a9059cbb"
+        );
+    }
+}
@@ -0,0 +1,773 @@
+use std::collections::HashMap;
+
+use bytes::Bytes;
+
+use super::attribute::StackAnnotation;
+use super::block_flow::{BlockFlow, BlockFlowItem, BlockFlowPushInner, Labels};
+use super::opcode::{DUP1, DUP16, JUMP, JUMPI, Opcodes, SWAP1, SWAP16, push_length};
+use crate::ast::{RBlock, WithAttributes};
+use crate::parser::error::{PestError, new_error_from_location};
+use crate::parser::parser::{Located, Location};
+use crate::parser::source::Sources;
+
+const STACK_LIMIT: usize = 1024;
+
+/// Start of a block or label, whose stack height must be the same however it is reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Target {
+    Block(usize),
+    Label(usize),
+}
+
+/// Position in the code, as the item reached in each inserted block - the first being a root.
+type Position = Vec<(usize, usize)>;
+
+struct StackAnalysis<'a> {
+    input: &'a Sources,
+    flows: &'a HashMap<usize, BlockFlow>,
+    r_blocks: &'a [Located<WithAttributes<Located<RBlock>>>],
+    labels: &'a Labels,
+    remapping: &'a [usize],
+    opcodes: &'a Opcodes,
+    positions: HashMap<Target, Position>,
+    heights: HashMap<Target, usize>,
+    /// Lowest stack height each block is entered with, and where it is entered.
+    entries: HashMap<usize, (usize, Location)>,
+    queue: Vec<(Position, usize, Target, Location)>,
+}
+
+/// Checks the stack height along the paths reachable from `main` without dynamic jumps, unless
+/// `stack_analysis` is disabled, and the stack effect declared by the attributes `stack`.
+#[allow(clippy::too_many_arguments)]
+pub fn check_stack(
+    input: &Sources,
+    flows: &HashMap<usize, BlockFlow>,
+    r_blocks: &[Located<WithAttributes<Located<RBlock>>>],
+    labels: &Labels,
+    remapping: &[usize],
+    opcodes: &Opcodes,
+    annotations: &[(usize, StackAnnotation)],
+    stack_analysis: bool,
+) -> Result<(), PestError> {
+    let mut analysis = StackAnalysis {
+        input,
+        flows,
+        r_blocks,
+        labels,
+        remapping,
+        opcodes,
+        positions: HashMap::new(),
+        heights: HashMap::new(),
+        entries: HashMap::new(),
+        queue: Vec::new(),
+    };
+    if stack_analysis {
+        for root in remapping {
+            if analysis.is_code(*root) {
+                analysis.find_positions(&mut vec![(*root, 0)]);
+            }
+        }
+
+        analysis.heights.insert(Target::Block(remapping[0]), 0);
+        analysis.queue.push((
+            vec![(remapping[0], 0)],
+            0,
+            Target::Block(remapping[0]),
+            r_blocks[remapping[0]].inner().name.location.clone(),
+        ));
+        while let Some((position, height, start, entered_at)) = analysis.queue.pop() {
+            analysis.walk(position, height, start, entered_at)?;
+        }
+    }
+
+    for (block_index, annotation) in annotations {
+        analysis.check_annotation(*block_index, annotation)?;
+    }
+
+    Ok(())
+}
+
+impl StackAnalysis<'_> {
+    fn is_code(&self, block_index: usize) -> bool {
+        let r_block = self.r_blocks[block_index].inner();
+        !r_block.data && r_block.table_width.is_none()
+    }
+
+    fn name(&self, target: Target) -> String {
+        match target {
+            Target::Block(block_index) => self.r_blocks[block_index].inner().name_str().to_owned(),
+            Target::Label(label_index) => self
+                .labels
+                .by_block
+                .iter()
+                .flat_map(|block_labels| block_labels.iter())
+                .find(|(_, index)| **index == label_index)
+                .map(|(name, _)| name.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Records where each block and label outside abstract blocks is placed.
+    fn find_positions(&mut self, position: &mut Position) {
+        let (block_index, _) = *position.last().unwrap();
+        if !self.r_blocks[block_index].inner().abstr {
+            self.positions
+                .insert(Target::Block(block_index), position.clone());
+        }
+        for (item_index, item) in self.flows[&block_index].items.iter().enumerate() {
+            position.last_mut().unwrap().1 = item_index;
+            match item {
+                BlockFlowItem::Label(label) if !self.labels.in_abstract[label.index] => {
+                    self.positions
+                        .insert(Target::Label(label.index), position.clone());
+                }
+                BlockFlowItem::BlockEsp(block_ref) | BlockFlowItem::BlockStar(block_ref) => {
+                    position.push((block_ref.index, 0));
+                    self.find_positions(position);
+                    position.pop();
+                }
+                _ => (),
+            }
+        }
+    }
+
+    /// Records the height at a target, and returns whether it was already reached.
+    fn reach(
+        &mut self,
+        target: Target,
+        height: usize,
+        location: &Location,
+    ) -> Result<bool, PestError> {
+        match self.heights.get(&target) {
+            Some(expected) if *expected == height => Ok(true),
+            Some(expected) => Err(new_error_from_location(
+                self.input,
+                location,
+                &format!(
+                    "`{}` is reached with {} item(s) on the stack here, but with {} elsewhere.",
+                    self.name(target),
+                    height,
+                    expected
+                ),
+            )),
+            None => {
+                self.heights.insert(target, height);
+                Ok(false)
+            }
+        }
+    }
+
+    /// Follows the execution from a target already reached, until it stops or reaches a target
+    /// already analyzed.
+    fn walk(
+        &mut self,
+        mut position: Position,
+        mut height: usize,
+        start: Target,
+        mut entered_at: Location,
+    ) -> Result<(), PestError> {
+        let mut start = Some(start);
+        let mut jump_target: Option<Target> = None;
+        // push data of a raw push, continuing in the next bytes
+        let mut skip = 0;
+        loop {
+            let (block_index, item_index) = *position.last().unwrap();
+            let items = &self.flows[&block_index].items;
+
+            let Some(item) = items.get(item_index) else {
+                position.pop();
+                match position.last_mut() {
+                    Some((_, parent_item_index)) => *parent_item_index += 1,
+                    None => {
+                        // falls through the next block placed in the code
+                        let root_index = self.remapping.iter().position(|i| *i == block_index);
+                        let Some(next) = root_index
+                            .and_then(|root_index| self.remapping.get(root_index + 1))
+                            .filter(|next| self.is_code(**next))
+                        else {
+                            return Ok(());
+                        };
+                        entered_at = self.r_blocks[*next].inner().name.location.clone();
+                        position.push((*next, 0));
+                    }
+                }
+                continue;
+            };
+
+            let r_block = self.r_blocks[block_index].inner();
+            if item_index == 0 {
+                match self.entries.get(&block_index) {
+                    Some((lowest, _)) if *lowest <= height => (),
+                    _ => {
+                        self.entries
+                            .insert(block_index, (height, entered_at.clone()));
+                    }
+                }
+            }
+            if item_index == 0 && !r_block.abstr {
+                jump_target = None;
+                let target = Target::Block(block_index);
+                if start.take_if(|start| *start == target).is_none()
+                    && self.reach(target, height, &r_block.name.location)?
+                {
+                    return Ok(());
+                }
+            }
+
+            match item {
+                BlockFlowItem::Label(label) => {
+                    jump_target = None;
+                    let target = Target::Label(label.index);
+                    if !self.labels.in_abstract[label.index]
+                        && start.take_if(|start| *start == target).is_none()
+                        && self.reach(target, height, &label.location)?
+                    {
+                        return Ok(());
+                    }
+                }
+                BlockFlowItem::Bytes(bytes) => {
+                    for op in bytes.iter().copied() {
+                        if skip > 0 {
+                            skip -= 1;
+                            continue;
+                        }
+                        skip = push_length(op).unwrap_or(0);
+                        let Some((inputs, outputs)) = self.opcodes.stack_effect(op) else {
+                            return Ok(());
+                        };
+                        height = self.apply(height, inputs, outputs, op, &bytes.location)?;
+                        let custom = self.opcodes.is_custom(op);
+                        if !custom
+                            && (op == JUMP || op == JUMPI)
+                            && let Some(target) = jump_target
+                            && !self.reach(target, height, &bytes.location)?
+                            && let Some(target_position) = self.positions.get(&target)
+                        {
+                            self.queue.push((
+                                target_position.clone(),
+                                height,
+                                target,
+                                bytes.location.clone(),
+                            ));
+                        }
+                        jump_target = None;
+                        if self.opcodes.is_terminating(op) {
+                            return Ok(());
+                        }
+                    }
+                }
+                BlockFlowItem::Push(push) => {
+                    height = self.apply(height, 0, 1, 0, &push.location)?;
+                    jump_target = match &push.inner {
+                        BlockFlowPushInner::BlockPc(index)
+                            if self.positions.contains_key(&Target::Block(*index)) =>
+                        {
+                            Some(Target::Block(*index))
+                        }
+                        BlockFlowPushInner::LabelPc(index, _)
+                            if self.positions.contains_key(&Target::Label(*index)) =>
+                        {
+                            Some(Target::Label(*index))
+                        }
+                        _ => None,
+                    };
+                    position.last_mut().unwrap().1 += 1;
+                    continue;
+                }
+                BlockFlowItem::BlockEsp(block_ref) | BlockFlowItem::BlockStar(block_ref) => {
+                    jump_target = None;
+                    entered_at = block_ref.location.clone();
+                    position.push((block_ref.index, 0));
+                    continue;
+                }
+                // the code of another contract is data
+                BlockFlowItem::Contract(_) | BlockFlowItem::Initcode(_) => return Ok(()),
+                BlockFlowItem::TableEntry(_) => (),
+                // resolved before the analysis
+                BlockFlowItem::StackSlot(_) | BlockFlowItem::StackLayout(_) => unreachable!(),
+            }
+            position.last_mut().unwrap().1 += 1;
+        }
+    }
+
+    fn apply(
+        &self,
+        height: usize,
+        inputs: usize,
+        outputs: usize,
+        op: u8,
+        location: &Location,
+    ) -> Result<usize, PestError> {
+        if height < inputs {
+            return Err(new_error_from_location(
+                self.input,
+                location,
+                &format!(
+                    "Stack underflow: the opcode 0x{:02x} takes {} item(s), {} on the stack.",
+                    op, inputs, height
+                ),
+            ));
+        }
+        let height = height - inputs + outputs;
+        if height > STACK_LIMIT {
+            return Err(new_error_from_location(
+                self.input,
+                location,
+                &format!(
+                    "Stack overflow: the stack holds at most {} items.",
+                    STACK_LIMIT
+                ),
+            ));
+        }
+        Ok(height)
+    }
+
+    fn check_annotation(
+        &self,
+        block_index: usize,
+        annotation: &StackAnnotation,
+    ) -> Result<(), PestError> {
+        // blocks never used are not analyzed
+        if !self.flows.contains_key(&block_index) {
+            return Ok(());
+        }
+        let (mut lowest, mut height) = (0, 0);
+        relative_effect(
+            self.flows,
+            self.opcodes,
+            block_index,
+            &mut lowest,
+            &mut height,
+        );
+        let taken = (-lowest) as usize;
+        let inputs = annotation.inputs.unwrap_or(taken);
+        if inputs < taken {
+            return Err(new_error_from_location(
+                self.input,
+                &annotation.location,
+                &format!(
+                    "The block `{}` takes {} item(s) from the stack.",
+                    self.r_blocks[block_index].inner().name_str(),
+                    taken
+                ),
+            ));
+        }
+        if let Some(outputs) = annotation.outputs
+            && outputs as isize != inputs as isize + height
+        {
+            return Err(new_error_from_location(
+                self.input,
+                &annotation.location,
+                &format!(
+                    "The block `{}` leaves {} item(s) on the stack out of {}.",
+                    self.r_blocks[block_index].inner().name_str(),
+                    inputs as isize + height,
+                    inputs
+                ),
+            ));
+        }
+        // the height is only known where the walk enters the block
+        if let Some((height, entered_at)) = self.entries.get(&block_index)
+            && *height < inputs
+        {
+            return Err(new_error_from_location(
+                self.input,
+                entered_at,
+                &format!(
+                    "The block `{}` takes {} item(s) from the stack, {} on the stack here.",
+                    self.r_blocks[block_index].inner().name_str(),
+                    inputs,
+                    height
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Returns the lowest height reached by a block relatively to its start, and its height when it
+/// ends or stops the execution.
+fn relative_effect(
+    flows: &HashMap<usize, BlockFlow>,
+    opcodes: &Opcodes,
+    block_index: usize,
+    lowest: &mut isize,
+    height: &mut isize,
+) -> bool {
+    let mut skip = 0;
+    for item in &flows[&block_index].items {
+        match item {
+            BlockFlowItem::Bytes(bytes) => {
+                for op in bytes.iter().copied() {
+                    if skip > 0 {
+                        skip -= 1;
+                        continue;
+                    }
+                    skip = push_length(op).unwrap_or(0);
+                    let Some((inputs, outputs)) = opcodes.stack_effect(op) else {
+                        return true;
+                    };
+                    *height -= inputs as isize;
+                    *lowest = (*lowest).min(*height);
+                    *height += outputs as isize;
+                    if opcodes.is_terminating(op) {
+                        return true;
+                    }
+                }
+            }
+            BlockFlowItem::Push(_) => *height += 1,
+            BlockFlowItem::StackSlot(slot) if !slot.swap => *height += 1,
+            BlockFlowItem::BlockEsp(block_ref) | BlockFlowItem::BlockStar(block_ref) => {
+                if relative_effect(flows, opcodes, block_ref.index, lowest, height) {
+                    return true;
+                }
+            }
+            BlockFlowItem::Contract(_) | BlockFlowItem::Initcode(_) => return true,
+            BlockFlowItem::Label(_)
+            | BlockFlowItem::TableEntry(_)
+            | BlockFlowItem::StackSlot(_)
+            | BlockFlowItem::StackLayout(_) => (),
+        }
+    }
+    false
+}
+
+/// Replaces the `dup` and `swap` of named items by the matching opcodes, following the names
+/// declared by the attributes `stack` through the effect of each item of the blocks.
+pub fn resolve_stack_slots(
+    input: &Sources,
+    flows: &mut HashMap<usize, BlockFlow>,
+    opcodes: &Opcodes,
+    annotations: &[(usize, StackAnnotation)],
+) -> Result<(), PestError> {
+    let mut block_indexes = flows.keys().copied().collect::<Vec<_>>();
+    block_indexes.sort_unstable();
+    for block_index in block_indexes {
+        let layout = annotations
+            .iter()
+            .find(|(index, _)| *index == block_index)
+            .map(|(_, annotation)| annotation.layout.as_slice())
+            .unwrap_or_default();
+        if !flows[&block_index].items.iter().any(|item| {
+            matches!(
+                item,
+                BlockFlowItem::StackSlot(_) | BlockFlowItem::StackLayout(_)
+            )
+        }) {
+            continue;
+        }
+        let items = resolve_block_slots(input, flows, opcodes, block_index, layout)?;
+        flows.get_mut(&block_index).unwrap().items = items;
+    }
+    Ok(())
+}
+
+fn resolve_block_slots(
+    input: &Sources,
+    flows: &HashMap<usize, BlockFlow>,
+    opcodes: &Opcodes,
+    block_index: usize,
+    layout: &[String],
+) -> Result<Vec<BlockFlowItem>, PestError> {
+    // names of the items known on top of the stack, the last being the top
+    let mut stack = layout.iter().rev().cloned().map(Some).collect::<Vec<_>>();
+    let mut items = Vec::with_capacity(flows[&block_index].items.len());
+    let mut skip = 0;
+    for item in &flows[&block_index].items {
+        match item {
+            BlockFlowItem::Bytes(bytes) => {
+                for op in bytes.iter().copied() {
+                    if skip > 0 {
+                        skip -= 1;
+                        continue;
+                    }
+                    skip = push_length(op).unwrap_or(0);
+                    let len = stack.len();
+                    match op {
+                        DUP1..=DUP16 if !opcodes.is_custom(op) => {
+                            let depth = (op - DUP1 + 1) as usize;
+                            let name = len.checked_sub(depth).and_then(|i| stack[i].clone());
+                            stack.push(name);
+                        }
+                        SWAP1..=SWAP16 if !opcodes.is_custom(op) => {
+                            let depth = (op - SWAP1 + 1) as usize;
+                            if depth < len {
+                                stack.swap(len - 1, len - 1 - depth);
+                            } else if let Some(top) = stack.last_mut() {
+                                *top = None;
+                            }
+                        }
+                        _ => match opcodes.stack_effect(op) {
+                            Some((inputs, outputs)) => {
+                                stack.truncate(len.saturating_sub(inputs));
+                                stack.extend(std::iter::repeat_n(None, outputs));
+                            }
+                            None => stack.clear(),
+                        },
+                    }
+                    // the next items are only reached by a jump
+                    if opcodes.is_terminating(op) {
+                        stack.clear();
+                    }
+                }
+            }
+            BlockFlowItem::Push(_) => stack.push(None),
+            BlockFlowItem::BlockEsp(block_ref) | BlockFlowItem::BlockStar(block_ref) => {
+                let (mut lowest, mut height) = (0, 0);
+                if relative_effect(flows, opcodes, block_ref.index, &mut lowest, &mut height) {
+                    stack.clear();
+                } else {
+                    stack.truncate(stack.len().saturating_sub((-lowest) as usize));
+                    stack.extend(std::iter::repeat_n(None, (height - lowest) as usize));
+                }
+            }
+            BlockFlowItem::Contract(_) | BlockFlowItem::Initcode(_) => stack.clear(),
+            BlockFlowItem::Label(_) | BlockFlowItem::TableEntry(_) => (),
+            BlockFlowItem::StackSlot(slot) => {
+                let len = stack.len();
+                let Some(index) = stack
+                    .iter()
+                    .rposition(|name| name.as_deref() == Some(slot.name.as_str()))
+                else {
+                    return Err(new_error_from_location(
+                        input,
+                        &slot.location,
+                        &format!("`{}` is not on the stack.", slot.name),
+                    ));
+                };
+                let depth = len - index;
+                let op = if slot.swap {
+                    if depth == 1 {
+                        return Err(new_error_from_location(
+                            input,
+                            &slot.location,
+                            &format!("`{}` is already on top of the stack.", slot.name),
+                        ));
+                    }
+                    if depth > 17 {
+                        return Err(new_error_from_location(
+                            input,
+                            &slot.location,
+                            &format!(
+                                "`{}` is {} items deep, out of the reach of `swap16`.",
+                                slot.name, depth
+                            ),
+                        ));
+                    }
+                    stack.swap(len - 1, index);
+                    SWAP1 + (depth - 2) as u8
+                } else {
+                    if depth > 16 {
+                        return Err(new_error_from_location(
+                            input,
+                            &slot.location,
+                            &format!(
+                                "`{}` is {} items deep, out of the reach of `dup16`.",
+                                slot.name, depth
+                            ),
+                        ));
+                    }
+                    stack.push(Some(slot.name.clone()));
+                    DUP1 + (depth - 1) as u8
+                };
+                items.push(BlockFlowItem::Bytes(Located {
+                    location: slot.location.clone(),
+                    inner: Bytes::copy_from_slice(&[op]),
+                }));
+                continue;
+            }
+            BlockFlowItem::StackLayout(layout) => {
+                for (position, name) in layout.iter().enumerate() {
+                    let len = stack.len();
+                    if position >= len {
+                        // names an item below the ones known
+                        stack.insert(0, Some(name.clone()));
+                        continue;
+                    }
+                    let slot = &mut stack[len - 1 - position];
+                    if let Some(tracked) = slot
+                        && tracked != name
+                    {
+                        return Err(new_error_from_location(
+                            input,
+                            &layout.location,
+                            &format!(
+                                "The item {} of the stack is `{}`, not `{}`.",
+                                position + 1,
+                                tracked,
+                                name
+                            ),
+                        ));
+                    }
+                    *slot = Some(name.clone());
+                }
+                continue;
+            }
+        }
+        items.push(item.clone());
+    }
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compile::file::{compile_source, compile_sources};
+    use crate::compile::settings::CompilerSettings;
+
+    #[test]
+    fn test_underflow() {
+        let err = compile_source(
+            "contract C {
+                block main { push(0x01) add }
+            }",
+            "C",
+        )
+        .unwrap_err();
+        assert!(err.contains("main.mep:2:41"), "{}", err);
+        assert!(
+            err.contains("Stack underflow: the opcode 0x01 takes 2 item(s), 1 on the stack."),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_height_mismatch() {
+        let err = compile_source(
+            "contract C {
+                block main { push(0x01) push(t.pc) jumpi caller push(t.pc) jump }
+                block t { jumpdest stop }
+            }",
+            "C",
+        )
+        .unwrap_err();
+        assert!(err.contains("main.mep:2:76"), "{}", err);
+        assert!(
+            err.contains("`t` is reached with 1 item(s) on the stack here, but with 0 elsewhere."),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_block_annotation() {
+        let source = |inputs: usize| {
+            format!(
+                "contract C {{
+                    block main {{ caller caller &f pop }}
+                    #[stack(in = {}, out = 1)]
+                    abstract block f {{ push(0x01) add add }}
+                }}",
+                inputs
+            )
+        };
+        let artifacts = compile_source(&source(2), "C").unwrap();
+        assert_eq!(hex::encode(artifacts.main_bytecode()), "33336001010150");
+
+        let err = compile_source(&source(1), "C").unwrap_err();
+        assert!(err.contains("main.mep:3:21"), "{}", err);
+        assert!(
+            err.contains("The block `f` takes 2 item(s) from the stack."),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_block_annotation_entry_height() {
+        let source = |items: &str| {
+            format!(
+                "contract C {{
+                    block main {{ {} &f stop }}
+                    #[stack(in = 2, out = 2)]
+                    abstract block f {{ pop caller }}
+                }}",
+                items
+            )
+        };
+        let artifacts = compile_source(&source("caller caller"), "C").unwrap();
+        assert_eq!(hex::encode(artifacts.main_bytecode()), "3333503300");
+
+        let err = compile_source(&source("caller"), "C").unwrap_err();
+        assert!(err.contains("main.mep:2:41"), "{}", err);
+        assert!(
+            err.contains("The block `f` takes 2 item(s) from the stack, 1 on the stack here."),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_stack_analysis_disabled() {
+        let source = "contract C {
+            block main { push(0x01) push(t.pc) jumpi caller push(t.pc) jump }
+            block t { jumpdest stop }
+        }";
+        let settings = CompilerSettings {
+            stack_analysis: false,
+            ..Default::default()
+        };
+        let artifacts = compile_sources(&[("main.mep", source)], "C", settings).unwrap();
+        assert_eq!(
+            hex::encode(artifacts.main_bytecode()),
+            "6001600957336009565b00"
+        );
+    }
+
+    #[test]
+    fn test_named_items() {
+        let artifacts = compile_source(
+            "contract C {
+                block main { push(0x02) push(0x01) &sub_in_place pop pop stop }
+
+                #[stack(a, b)]
+                abstract block sub_in_place {
+                    dup(b) dup(a) sub
+                    #[stack(diff, a, b)]
+                    swap(b) pop
+                }
+            }",
+            "C",
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(artifacts.main_bytecode()),
+            "600260018181039150505000"
+        );
+    }
+
+    #[test]
+    fn test_named_item_reach() {
+        let source = |depth: usize, function: &str| {
+            format!(
+                "contract C {{
+                    block main {{ push(0x01) &f }}
+                    #[stack(a)]
+                    abstract block f {{ {}{}(a) }}
+                }}",
+                "caller ".repeat(depth),
+                function
+            )
+        };
+        let bytecode = |depth: usize, function: &str| {
+            let artifacts = compile_source(&source(depth, function), "C").unwrap();
+            hex::encode(&artifacts.main_bytecode()[2 + depth..])
+        };
+        assert_eq!(bytecode(0, "dup"), "80");
+        assert_eq!(bytecode(15, "dup"), "8f");
+        assert_eq!(bytecode(1, "swap"), "90");
+        assert_eq!(bytecode(16, "swap"), "9f");
+
+        for (depth, function) in [(16, "dup"), (17, "swap")] {
+            let err = compile_source(&source(depth, function), "C").unwrap_err();
+            assert!(
+                err.contains(&format!("out of the reach of `{}16`", function)),
+                "{}",
+                err
+            );
+        }
+
+        let err = compile_source(&source(1, "dup").replace("dup(a)", "dup(b)"), "C").unwrap_err();
+        assert!(err.contains("`b` is not on the stack."), "{}", err);
+    }
+}
@@ -1,8 +1,8 @@
-use std::ops::{Deref, DerefMut};
+use std::ops::{BitAnd, BitOr, BitXor, Deref, DerefMut, Not};
 
 use bytes::Bytes;
 
-#[derive(Debug, Clone, Default, Hash, Eq, PartialEq)]
+#[derive(Debug, Clone, Default, Hash, Eq, PartialEq, PartialOrd, Ord)]
 pub struct Bytes32(pub [u8; 32]);
 
 impl Deref for Bytes32 {
@@ -50,6 +50,30 @@ impl Bytes32 {
         i
     }
 
+    pub fn leading_zero_bits(&self) -> usize {
+        let limbs = self.to_limbs();
+        let mut res = 0;
+        for limb in limbs.iter().rev() {
+            res += limb.leading_zeros() as usize;
+            if *limb != 0 {
+                break;
+            }
+        }
+        res
+    }
+
+    pub fn trailing_zero_bits(&self) -> usize {
+        let limbs = self.to_limbs();
+        let mut res = 0;
+        for limb in limbs.iter() {
+            res += limb.trailing_zeros() as usize;
+            if *limb != 0 {
+                break;
+            }
+        }
+        res
+    }
+
     pub fn is_zero(&self) -> bool {
         self.leading_zeros() == 32
     }
@@ -57,4 +81,247 @@ impl Bytes32 {
     pub fn right_content(&self) -> &[u8] {
         &self[self.leading_zeros()..32]
     }
+
+    pub fn from_usize(value: usize) -> Self {
+        Self::from_limbs([value as u64, 0, 0, 0])
+    }
+
+    pub fn to_usize(&self) -> Option<usize> {
+        let limbs = self.to_limbs();
+        if limbs[1..].iter().any(|limb| *limb != 0) {
+            return None;
+        }
+        usize::try_from(limbs[0]).ok()
+    }
+
+    pub fn checked_add(&self, other: &Self) -> Option<Self> {
+        let (res, overflow) = self.overflowing_add(other);
+        (!overflow).then_some(res)
+    }
+
+    pub fn overflowing_add(&self, other: &Self) -> (Self, bool) {
+        let (a, b) = (self.to_limbs(), other.to_limbs());
+        let mut res = [0u64; 4];
+        let mut carry = false;
+        for i in 0..4 {
+            let (sum, carry_a) = a[i].overflowing_add(b[i]);
+            let (sum, carry_b) = sum.overflowing_add(carry as u64);
+            res[i] = sum;
+            carry = carry_a || carry_b;
+        }
+        (Self::from_limbs(res), carry)
+    }
+
+    pub fn checked_sub(&self, other: &Self) -> Option<Self> {
+        let (res, overflow) = self.overflowing_sub(other);
+        (!overflow).then_some(res)
+    }
+
+    pub fn overflowing_sub(&self, other: &Self) -> (Self, bool) {
+        let (a, b) = (self.to_limbs(), other.to_limbs());
+        let mut res = [0u64; 4];
+        let mut borrow = false;
+        for i in 0..4 {
+            let (diff, borrow_a) = a[i].overflowing_sub(b[i]);
+            let (diff, borrow_b) = diff.overflowing_sub(borrow as u64);
+            res[i] = diff;
+            borrow = borrow_a || borrow_b;
+        }
+        (Self::from_limbs(res), borrow)
+    }
+
+    pub fn checked_mul(&self, other: &Self) -> Option<Self> {
+        let (a, b) = (self.to_limbs(), other.to_limbs());
+        let mut res = [0u64; 8];
+        for i in 0..4 {
+            let mut carry = 0u128;
+            for j in 0..4 {
+                let product = (a[i] as u128) * (b[j] as u128) + (res[i + j] as u128) + carry;
+                res[i + j] = product as u64;
+                carry = product >> 64;
+            }
+            res[i + 4] = carry as u64;
+        }
+        if res[4..].iter().any(|limb| *limb != 0) {
+            return None;
+        }
+        Some(Self::from_limbs([res[0], res[1], res[2], res[3]]))
+    }
+
+    /// Returns the quotient and the remainder of the division, or `None` if `other` is zero.
+    pub fn div_rem(&self, other: &Self) -> Option<(Self, Self)> {
+        if other.is_zero() {
+            return None;
+        }
+        let mut quotient = Self::default();
+        let mut remainder = Self::default();
+        for bit in 0..256 {
+            remainder = remainder.shl(1);
+            if self[bit / 8] & (0x80 >> (bit % 8)) != 0 {
+                remainder[31] |= 1;
+            }
+            if remainder >= *other {
+                remainder = remainder.overflowing_sub(other).0;
+                quotient[bit / 8] |= 0x80 >> (bit % 8);
+            }
+        }
+        Some((quotient, remainder))
+    }
+
+    /// Left shift, or `None` if non-zero bits are shifted out.
+    pub fn checked_shl(&self, shift: usize) -> Option<Self> {
+        let res = self.shl(shift);
+        (res.shr(shift) == *self).then_some(res)
+    }
+
+    pub fn shl(&self, shift: usize) -> Self {
+        if shift >= 256 {
+            return Self::default();
+        }
+        let limbs = self.to_limbs();
+        let (limb_shift, bit_shift) = (shift / 64, shift % 64);
+        let mut res = [0u64; 4];
+        for i in limb_shift..4 {
+            res[i] = limbs[i - limb_shift] << bit_shift;
+            if bit_shift > 0 && i > limb_shift {
+                res[i] |= limbs[i - limb_shift - 1] >> (64 - bit_shift);
+            }
+        }
+        Self::from_limbs(res)
+    }
+
+    pub fn shr(&self, shift: usize) -> Self {
+        if shift >= 256 {
+            return Self::default();
+        }
+        let limbs = self.to_limbs();
+        let (limb_shift, bit_shift) = (shift / 64, shift % 64);
+        let mut res = [0u64; 4];
+        for i in 0..4 - limb_shift {
+            res[i] = limbs[i + limb_shift] >> bit_shift;
+            if bit_shift > 0 && i + limb_shift + 1 < 4 {
+                res[i] |= limbs[i + limb_shift + 1] << (64 - bit_shift);
+            }
+        }
+        Self::from_limbs(res)
+    }
+
+    /// Little-endian 64 bits limbs.
+    fn to_limbs(&self) -> [u64; 4] {
+        let mut limbs = [0u64; 4];
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            let start = 32 - 8 * (i + 1);
+            *limb = u64::from_be_bytes(self[start..start + 8].try_into().unwrap());
+        }
+        limbs
+    }
+
+    fn from_limbs(limbs: [u64; 4]) -> Self {
+        let mut res = [0u8; 32];
+        for (i, limb) in limbs.iter().enumerate() {
+            let start = 32 - 8 * (i + 1);
+            res[start..start + 8].copy_from_slice(&limb.to_be_bytes());
+        }
+        Self(res)
+    }
+}
+
+impl Not for &Bytes32 {
+    type Output = Bytes32;
+
+    fn not(self) -> Self::Output {
+        Bytes32(self.0.map(|byte| !byte))
+    }
+}
+
+impl BitAnd for &Bytes32 {
+    type Output = Bytes32;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Bytes32(std::array::from_fn(|i| self[i] & rhs[i]))
+    }
+}
+
+impl BitOr for &Bytes32 {
+    type Output = Bytes32;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Bytes32(std::array::from_fn(|i| self[i] | rhs[i]))
+    }
+}
+
+impl BitXor for &Bytes32 {
+    type Output = Bytes32;
+
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        Bytes32(std::array::from_fn(|i| self[i] ^ rhs[i]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pow2(exponent: usize) -> Bytes32 {
+        Bytes32::from_usize(1).shl(exponent)
+    }
+
+    fn word(hex: &str) -> Bytes32 {
+        Bytes32::from_bytes(&Bytes::from(hex::decode(hex).unwrap()), true).unwrap()
+    }
+
+    #[test]
+    fn test_checked_mul() {
+        assert_eq!(pow2(128).checked_mul(&pow2(128)), None);
+        assert_eq!(pow2(128).checked_mul(&pow2(127)), Some(pow2(255)));
+        assert_eq!(
+            word("ffffffffffffffff").checked_mul(&word("ffffffffffffffff")),
+            Some(word("fffffffffffffffe0000000000000001"))
+        );
+        assert_eq!(
+            pow2(255).checked_mul(&Bytes32::default()),
+            Some(Bytes32::default())
+        );
+    }
+
+    #[test]
+    fn test_div_rem() {
+        let x = word("0123456789abcdef0123456789abcdef0123456789abcdef");
+        let one = Bytes32::from_usize(1);
+        assert_eq!(x.div_rem(&one), Some((x.clone(), Bytes32::default())));
+        assert_eq!(x.div_rem(&x), Some((one, Bytes32::default())));
+        assert_eq!(x.div_rem(&Bytes32::default()), None);
+        assert_eq!(
+            Bytes32::from_usize(100).div_rem(&Bytes32::from_usize(7)),
+            Some((Bytes32::from_usize(14), Bytes32::from_usize(2)))
+        );
+        assert_eq!(
+            pow2(255).div_rem(&pow2(64)),
+            Some((pow2(191), Bytes32::default()))
+        );
+    }
+
+    #[test]
+    fn test_shifts() {
+        let x = word("8000000000000001");
+        assert_eq!(x.shl(0), x);
+        assert_eq!(x.shr(0), x);
+        assert_eq!(x.shl(63), word("40000000000000008000000000000000"));
+        assert_eq!(x.shl(64), word("80000000000000010000000000000000"));
+        assert_eq!(x.shr(63), Bytes32::from_usize(1));
+        assert_eq!(x.shl(64).shr(64), x);
+        assert_eq!(Bytes32::from_usize(1).shl(255), pow2(255));
+        assert_eq!(pow2(255).shr(255), Bytes32::from_usize(1));
+        assert_eq!(x.shl(256), Bytes32::default());
+        assert_eq!(pow2(255).shr(256), Bytes32::default());
+
+        assert_eq!(x.checked_shl(0), Some(x.clone()));
+        assert_eq!(Bytes32::from_usize(1).checked_shl(255), Some(pow2(255)));
+        assert_eq!(Bytes32::from_usize(2).checked_shl(255), None);
+        assert_eq!(Bytes32::from_usize(1).checked_shl(256), None);
+        assert_eq!(
+            Bytes32::default().checked_shl(256),
+            Some(Bytes32::default())
+        );
+    }
 }